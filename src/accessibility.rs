@@ -0,0 +1,97 @@
+use indexmap::IndexMap;
+
+use crate::models::{Color, Flavor, Palette};
+
+/// Colors that are checked as text on top of the backgrounds, alongside all accents.
+const TEXT_COLORS: [&str; 3] = ["text", "subtext1", "subtext0"];
+
+/// Colors that are used as backgrounds.
+const BACKGROUND_COLORS: [&str; 6] = [
+    "base", "mantle", "crust", "surface0", "surface1", "surface2",
+];
+
+/// Contrast of every foreground color against every background color, keyed by flavor.
+pub type Report = IndexMap<String, Vec<Pair>>;
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct Pair {
+    pub foreground: String,
+    pub background: String,
+    pub ratio: f64,
+    /// WCAG 2.x level AA for normal text (4.5:1)
+    pub aa: bool,
+    /// WCAG 2.x level AAA for normal text (7:1)
+    pub aaa: bool,
+    /// WCAG 2.x level AA for large text (3:1)
+    pub aa_large: bool,
+    /// WCAG 2.x level AAA for large text (4.5:1)
+    pub aaa_large: bool,
+}
+
+impl Pair {
+    fn new(foreground: &Color, background: &Color) -> Self {
+        let ratio = foreground.contrast(background);
+        Self {
+            foreground: foreground.identifier.clone(),
+            background: background.identifier.clone(),
+            ratio,
+            aa: ratio >= 4.5,
+            aaa: ratio >= 7.0,
+            aa_large: ratio >= 3.0,
+            aaa_large: ratio >= 4.5,
+        }
+    }
+
+    /// The highest WCAG level this pair passes, as a short label.
+    #[must_use]
+    pub const fn level(&self) -> &'static str {
+        if self.aaa {
+            "AAA"
+        } else if self.aa {
+            "AA"
+        } else if self.aa_large {
+            "AA Large"
+        } else {
+            "Fail"
+        }
+    }
+}
+
+#[must_use]
+pub fn foregrounds(flavor: &Flavor) -> Vec<&Color> {
+    flavor
+        .iter()
+        .map(|(_, color)| color)
+        .filter(|color| color.accent || TEXT_COLORS.contains(&color.identifier.as_str()))
+        .collect()
+}
+
+#[must_use]
+pub fn backgrounds(flavor: &Flavor) -> Vec<&Color> {
+    flavor
+        .iter()
+        .map(|(_, color)| color)
+        .filter(|color| BACKGROUND_COLORS.contains(&color.identifier.as_str()))
+        .collect()
+}
+
+/// Build a contrast report for every flavor in the palette, or just `only_flavor`.
+#[must_use]
+pub fn build_report(palette: &Palette, only_flavor: Option<&str>) -> Report {
+    palette
+        .iter()
+        .filter(|(identifier, _)| only_flavor.is_none_or(|f| f == identifier.as_str()))
+        .map(|(identifier, flavor)| {
+            let pairs = foregrounds(flavor)
+                .into_iter()
+                .flat_map(|fg| {
+                    backgrounds(flavor)
+                        .into_iter()
+                        .map(move |bg| Pair::new(fg, bg))
+                })
+                .collect();
+            (identifier.clone(), pairs)
+        })
+        .collect()
+}
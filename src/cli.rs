@@ -5,6 +5,7 @@ use std::{
 
 use clap::Parser;
 use clap_stdin::FileOrStdin;
+use indexmap::IndexMap;
 
 type ValueMap = HashMap<String, serde_json::Value>;
 
@@ -12,14 +13,41 @@ type ValueMap = HashMap<String, serde_json::Value>;
 #[command(version, about)]
 pub struct Args {
     /// Path to the template file, or - for stdin
-    #[arg(required_unless_present = "list_functions")]
+    #[arg(required_unless_present_any = ["list_functions", "accessibility_report"])]
     pub template: Option<FileOrStdin>,
 
-    /// Render a single flavor instead of all four
+    /// Render a single flavor instead of all of them
+    ///
+    /// This can be one of the palette's flavors, or one derived in the
+    /// template's frontmatter.
     #[arg(long, short)]
-    pub flavor: Option<Flavor>,
+    pub flavor: Option<String>,
+
+    /// Use a custom palette from a JSON or YAML file instead of Catppuccin's
+    ///
+    /// The file has the same shape as the `flavors` template variable: a
+    /// `flavors` object keyed by flavor identifier, each with a `name`,
+    /// `dark` and `colors`, and each color with a `name`, `hex` and `accent`.
+    #[arg(long, value_name = "FILE")]
+    pub palette: Option<PathBuf>,
 
     /// Set color overrides
+    ///
+    /// A JSON object (or path to a JSON file) with `all`, `latte`, `frappe`,
+    /// `macchiato` and `mocha` keys, each mapping color identifiers to a hex
+    /// string (3, 4, 6 or 8 digits, optionally starting with `#`) or a
+    /// `{"hex", "name", "accent"}` object. The object form also adds custom
+    /// colors that aren't in the palette.
+    ///
+    /// These are layered on top of any `color_overrides` in the template's
+    /// frontmatter. From lowest to highest precedence, a color is taken from:
+    /// frontmatter `all`, frontmatter flavor, `--color-overrides` `all`, then
+    /// `--color-overrides` flavor. So an `all` override given here replaces a
+    /// flavor-specific one from the frontmatter.
+    ///
+    /// A flavor derived in the frontmatter starts from the flavor it's based
+    /// on with all of that flavor's overrides applied, then takes its own
+    /// `colors`, then any overrides keyed by its own identifier.
     #[arg(long, value_parser = json_map::<ColorOverrides>)]
     pub color_overrides: Option<ColorOverrides>,
 
@@ -43,7 +71,14 @@ pub struct Args {
     #[arg(short, long)]
     pub list_functions: bool,
 
-    /// Output format of --list-functions
+    /// Print a WCAG contrast report of text & accent colors against background colors
+    ///
+    /// No template is read in this mode, so flavors derived in a template's
+    /// frontmatter are not available.
+    #[arg(long)]
+    pub accessibility_report: bool,
+
+    /// Output format of --list-functions and --accessibility-report
     #[arg(short, long, default_value = "json")]
     pub output_format: OutputFormat,
 }
@@ -64,37 +99,128 @@ enum Error {
     },
 }
 
-#[derive(Copy, Clone, Debug, clap::ValueEnum)]
-pub enum Flavor {
-    Latte,
-    Frappe,
-    Macchiato,
-    Mocha,
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct ColorOverrides {
+    /// Overrides applied to every flavor.
+    #[serde(default)]
+    pub all: IndexMap<String, ColorOverride>,
+    /// Overrides for individual flavors, keyed by flavor identifier.
+    #[serde(flatten)]
+    pub flavors: IndexMap<String, IndexMap<String, ColorOverride>>,
+}
+
+impl ColorOverrides {
+    /// Split off the overrides for the flavors matching `split`, returning
+    /// the rest (including `all`) and the split-off overrides.
+    #[must_use]
+    pub fn split_off(&self, split: impl Fn(&str) -> bool) -> (Self, Self) {
+        let (split, rest) = self
+            .flavors
+            .clone()
+            .into_iter()
+            .partition(|(flavor, _)| split(flavor));
+        (
+            Self {
+                all: self.all.clone(),
+                flavors: rest,
+            },
+            Self {
+                all: IndexMap::new(),
+                flavors: split,
+            },
+        )
+    }
+}
+
+/// A replacement for one of the palette's colors, or a new custom color.
+///
+/// Built-in colors can be replaced with a plain hex string. Custom colors
+/// must use the object form, which can also set the color's name and
+/// whether it is an accent.
+#[derive(Clone, Debug)]
+pub enum ColorOverride {
+    Hex(String),
+    Color {
+        hex: String,
+        name: Option<String>,
+        accent: Option<bool>,
+    },
 }
 
-impl From<Flavor> for catppuccin::FlavorName {
-    fn from(val: Flavor) -> Self {
-        match val {
-            Flavor::Latte => Self::Latte,
-            Flavor::Frappe => Self::Frappe,
-            Flavor::Macchiato => Self::Macchiato,
-            Flavor::Mocha => Self::Mocha,
+// deserialized by hand rather than as an untagged enum, so that mistakes in
+// the object form (such as a misspelled key) are reported instead of being
+// hidden behind "data did not match any variant".
+impl<'de> serde::Deserialize<'de> for ColorOverride {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Object {
+            hex: String,
+            name: Option<String>,
+            accent: Option<bool>,
+        }
+
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ColorOverride;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a hex string, or an object with `hex`, `name` and `accent` keys")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, hex: &str) -> Result<Self::Value, E> {
+                Ok(ColorOverride::Hex(hex.to_string()))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let Object { hex, name, accent } = serde::Deserialize::deserialize(
+                    serde::de::value::MapAccessDeserializer::new(map),
+                )?;
+                Ok(ColorOverride::Color { hex, name, accent })
+            }
         }
+
+        deserializer.deserialize_any(Visitor)
     }
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
-pub struct ColorOverrides {
-    #[serde(default)]
-    pub all: HashMap<String, String>,
-    #[serde(default)]
-    pub latte: HashMap<String, String>,
-    #[serde(default)]
-    pub frappe: HashMap<String, String>,
-    #[serde(default)]
-    pub macchiato: HashMap<String, String>,
-    #[serde(default)]
-    pub mocha: HashMap<String, String>,
+impl ColorOverride {
+    #[must_use]
+    pub fn hex(&self) -> &str {
+        match self {
+            Self::Hex(hex) | Self::Color { hex, .. } => hex,
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Hex(_) => None,
+            Self::Color { name, .. } => name.as_deref(),
+        }
+    }
+
+    #[must_use]
+    pub const fn accent(&self) -> Option<bool> {
+        match self {
+            Self::Hex(_) => None,
+            Self::Color { accent, .. } => *accent,
+        }
+    }
+
+    /// Whether this override is in the object form, allowing it to define a
+    /// custom color.
+    #[must_use]
+    pub const fn is_custom(&self) -> bool {
+        matches!(self, Self::Color { .. })
+    }
 }
 
 #[derive(Clone, Copy, Debug, clap::ValueEnum)]
@@ -0,0 +1,722 @@
+//! Conversions between sRGB and the other color spaces whiskers works in.
+//!
+//! All functions here operate on plain `f64` triples. sRGB and linear sRGB
+//! channels are in the range 0..1, Oklab/Oklch follow Björn Ottosson's
+//! definition with lightness in 0..1 and hue in degrees.
+
+// the matrix maths below reads far better written out longhand.
+#![allow(clippy::suboptimal_flops, clippy::many_single_char_names)]
+
+/// Convert a gamma-encoded sRGB channel into linear light.
+#[must_use]
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear light channel into gamma-encoded sRGB.
+#[must_use]
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[must_use]
+pub fn linear_srgb_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = 0.412_221_470_8 * r + 0.536_332_536_3 * g + 0.051_445_992_9 * b;
+    let m = 0.211_903_498_2 * r + 0.680_699_545_1 * g + 0.107_396_956_6 * b;
+    let s = 0.088_302_461_9 * r + 0.281_718_837_6 * g + 0.629_978_700_5 * b;
+
+    let l = l.cbrt();
+    let m = m.cbrt();
+    let s = s.cbrt();
+
+    [
+        0.210_454_255_3 * l + 0.793_617_785_0 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205_0 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766_0 * s,
+    ]
+}
+
+#[must_use]
+pub fn oklab_to_linear_srgb([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let l_ = l + 0.396_337_777_4 * a + 0.215_803_757_3 * b;
+    let m_ = l - 0.105_561_345_8 * a - 0.063_854_172_8 * b;
+    let s_ = l - 0.089_484_177_5 * a - 1.291_485_548_0 * b;
+
+    let l = l_ * l_ * l_;
+    let m = m_ * m_ * m_;
+    let s = s_ * s_ * s_;
+
+    [
+        4.076_741_662_1 * l - 3.307_711_591_3 * m + 0.230_969_929_2 * s,
+        -1.268_438_004_6 * l + 2.609_757_401_1 * m - 0.341_319_396_5 * s,
+        -0.004_196_086_3 * l - 0.703_418_614_7 * m + 1.707_614_701_0 * s,
+    ]
+}
+
+#[must_use]
+pub fn srgb_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    linear_srgb_to_oklab([srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b)])
+}
+
+#[must_use]
+pub fn oklab_to_srgb(lab: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = oklab_to_linear_srgb(lab);
+    [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)]
+}
+
+/// Convert sRGB into HSL, with hue in degrees and saturation & lightness from 0 to 1.
+#[must_use]
+pub fn srgb_to_hsl([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = f64::midpoint(max, min);
+    let d = max - min;
+    if d == 0.0 {
+        return [0.0, 0.0, l];
+    }
+
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    #[allow(clippy::float_cmp)]
+    let h = if max == r {
+        ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    [h * 60.0, s, l]
+}
+
+/// Convert HSL into sRGB, with hue in degrees and saturation & lightness from 0 to 1.
+#[must_use]
+pub fn hsl_to_srgb([h, s, l]: [f64; 3]) -> [f64; 3] {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let m = l - c / 2.0;
+    let [r, g, b] = match h {
+        h if h < 1.0 => [c, x, 0.0],
+        h if h < 2.0 => [x, c, 0.0],
+        h if h < 3.0 => [0.0, c, x],
+        h if h < 4.0 => [0.0, x, c],
+        h if h < 5.0 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
+/// Chroma below this is considered achromatic, and its hue meaningless.
+const ACHROMATIC_THRESHOLD: f64 = 1e-6;
+
+#[must_use]
+pub fn oklab_to_oklch([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let c = a.hypot(b);
+    let h = if c < ACHROMATIC_THRESHOLD {
+        0.0
+    } else {
+        b.atan2(a).to_degrees().rem_euclid(360.0)
+    };
+    [l, c, h]
+}
+
+#[must_use]
+pub fn oklch_to_oklab([l, c, h]: [f64; 3]) -> [f64; 3] {
+    let (sin, cos) = h.to_radians().sin_cos();
+    [l, c * cos, c * sin]
+}
+
+/// Convert an Oklch color into sRGB, reducing chroma until it fits in gamut.
+///
+/// Lightness and hue are preserved, which keeps adjustments in Oklch
+/// perceptually predictable even when they push a color out of sRGB.
+#[must_use]
+pub fn oklch_to_srgb_gamut_mapped(lch: [f64; 3]) -> [f64; 3] {
+    const EPSILON: f64 = 1e-6;
+    let in_gamut = |rgb: [f64; 3]| rgb.iter().all(|c| (-EPSILON..=1.0 + EPSILON).contains(c));
+
+    let [l, c, h] = lch;
+    let l = l.clamp(0.0, 1.0);
+    let rgb = oklab_to_srgb(oklch_to_oklab([l, c, h]));
+    if in_gamut(rgb) {
+        return rgb.map(|c| c.clamp(0.0, 1.0));
+    }
+
+    let (mut lo, mut hi) = (0.0, c.max(0.0));
+    for _ in 0..24 {
+        let mid = f64::midpoint(lo, hi);
+        if in_gamut(oklab_to_srgb(oklch_to_oklab([l, mid, h]))) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    oklab_to_srgb(oklch_to_oklab([l, lo, h])).map(|c| c.clamp(0.0, 1.0))
+}
+
+/// The WCAG 2.x relative luminance of an sRGB color.
+#[must_use]
+pub fn relative_luminance(rgb: [f64; 3]) -> f64 {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// The WCAG 2.x contrast ratio between two relative luminances, from 1 to 21.
+#[must_use]
+pub fn contrast_ratio(a: f64, b: f64) -> f64 {
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// A type of color vision deficiency that can be simulated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cvd {
+    /// Normal color vision, for convenience when iterating over deficiencies.
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+/// Simulate how an sRGB color appears with a color vision deficiency.
+///
+/// Uses the Machado et al. (2009) matrices in linear light. `severity` from
+/// 0 to 1 blends between normal vision and full dichromacy.
+#[must_use]
+pub fn simulate_cvd(rgb: [f64; 3], cvd: Cvd, severity: f64) -> [f64; 3] {
+    let matrix = match cvd {
+        Cvd::None => return rgb,
+        Cvd::Protanopia => [
+            [0.152_286, 1.052_583, -0.204_868],
+            [0.114_503, 0.786_281, 0.099_216],
+            [-0.003_882, -0.048_116, 1.051_998],
+        ],
+        Cvd::Deuteranopia => [
+            [0.367_322, 0.860_646, -0.227_968],
+            [0.280_085, 0.672_501, 0.047_413],
+            [-0.011_820, 0.042_940, 0.968_881],
+        ],
+        Cvd::Tritanopia => [
+            [1.255_528, -0.076_749, -0.178_779],
+            [-0.078_411, 0.930_809, 0.147_602],
+            [0.004_733, 0.691_367, 0.303_900],
+        ],
+    };
+    let severity = severity.clamp(0.0, 1.0);
+    let linear = rgb.map(srgb_to_linear);
+    let simulated = matrix.map(|row| row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]);
+    [0, 1, 2].map(|i| linear_to_srgb(lerp(linear[i], simulated[i], severity)).clamp(0.0, 1.0))
+}
+
+/// Convert gamma-encoded sRGB into CIELAB, relative to the D65 white point.
+#[must_use]
+pub fn srgb_to_lab(rgb: [f64; 3]) -> [f64; 3] {
+    const WHITE: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+    const EPSILON: f64 = 216.0 / 24389.0;
+    const KAPPA: f64 = 24389.0 / 27.0;
+
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let xyz = [
+        0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+        0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b,
+        0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b,
+    ];
+    let [fx, fy, fz] = [0, 1, 2].map(|i| {
+        let t = xyz[i] / WHITE[i];
+        if t > EPSILON {
+            t.cbrt()
+        } else {
+            (KAPPA * t + 16.0) / 116.0
+        }
+    });
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// A formula for the perceptual difference between two colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+pub enum DeltaE {
+    /// CIE76: euclidean distance in CIELAB.
+    #[serde(rename = "76")]
+    Cie76,
+    /// CIE94, with the graphic arts weightings.
+    #[serde(rename = "94")]
+    Cie94,
+    /// CIEDE2000.
+    #[default]
+    #[serde(rename = "2000")]
+    Ciede2000,
+    /// Euclidean distance in Oklab.
+    #[serde(rename = "ok")]
+    Ok,
+}
+
+/// The difference between two sRGB colors using the given formula.
+#[must_use]
+pub fn delta_e(a: [f64; 3], b: [f64; 3], method: DeltaE) -> f64 {
+    let distance = |a: [f64; 3], b: [f64; 3]| {
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    };
+    match method {
+        DeltaE::Cie76 => distance(srgb_to_lab(a), srgb_to_lab(b)),
+        DeltaE::Cie94 => cie94(srgb_to_lab(a), srgb_to_lab(b)),
+        DeltaE::Ciede2000 => ciede2000(srgb_to_lab(a), srgb_to_lab(b)),
+        DeltaE::Ok => distance(srgb_to_oklab(a), srgb_to_oklab(b)),
+    }
+}
+
+fn cie94([l1, a1, b1]: [f64; 3], [l2, a2, b2]: [f64; 3]) -> f64 {
+    let c1 = a1.hypot(b1);
+    let c2 = a2.hypot(b2);
+    let dl = l1 - l2;
+    let dc = c1 - c2;
+    let dh2 = ((a1 - a2).powi(2) + (b1 - b2).powi(2) - dc.powi(2)).max(0.0);
+    let sc = 1.0 + 0.045 * c1;
+    let sh = 1.0 + 0.015 * c1;
+    (dl.powi(2) + (dc / sc).powi(2) + dh2 / sh.powi(2)).sqrt()
+}
+
+/// CIEDE2000, following Sharma, Wu and Dalal (2005).
+fn ciede2000([l1, a1, b1]: [f64; 3], [l2, a2, b2]: [f64; 3]) -> f64 {
+    let pow25_7 = 25f64.powi(7);
+    let c_bar = f64::midpoint(a1.hypot(b1), a2.hypot(b2));
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+    let chromatic = c1 * c2 != 0.0;
+
+    let dl = l2 - l1;
+    let dc = c2 - c1;
+    let dh = if !chromatic {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 > h1 {
+        h2 - h1 - 360.0
+    } else {
+        h2 - h1 + 360.0
+    };
+    let dh = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).to_radians().sin();
+
+    let l_bar = f64::midpoint(l1, l2);
+    let c_bar = f64::midpoint(c1, c2);
+    let h_bar = if !chromatic {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        f64::midpoint(h1, h2)
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let t =
+        1.0 - 0.17 * cos(h_bar - 30.0) + 0.24 * cos(2.0 * h_bar) + 0.32 * cos(3.0 * h_bar + 6.0)
+            - 0.20 * cos(4.0 * h_bar - 63.0);
+    let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let rc = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt();
+    let sl = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let sc = 1.0 + 0.045 * c_bar;
+    let sh = 1.0 + 0.015 * c_bar * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+    let (l, c, h) = (dl / sl, dc / sc, dh / sh);
+    (l.powi(2) + c.powi(2) + h.powi(2) + rt * c * h).sqrt()
+}
+
+/// sRGB channels and straight (not premultiplied) alpha, each from 0 to 1.
+pub type Rgba = ([f64; 3], f64);
+
+/// A color space in which two colors can be mixed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MixSpace {
+    #[default]
+    Srgb,
+    LinearSrgb,
+    Oklab,
+    Oklch,
+}
+
+/// Which way around the hue wheel to travel when mixing in a polar space.
+///
+/// These match the `<hue-interpolation-method>` values from CSS Color 4.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HueInterpolation {
+    #[default]
+    Shorter,
+    Longer,
+    Increasing,
+    Decreasing,
+}
+
+/// Linearly interpolate from `a` to `b` by `t`.
+#[must_use]
+pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    (b - a).mul_add(t, a)
+}
+
+/// Interpolate between two hues in degrees, travelling in the given direction.
+#[must_use]
+pub fn lerp_hue(a: f64, b: f64, t: f64, method: HueInterpolation) -> f64 {
+    let (mut a, mut b) = (a.rem_euclid(360.0), b.rem_euclid(360.0));
+    let delta = b - a;
+    match method {
+        HueInterpolation::Shorter => {
+            if delta > 180.0 {
+                a += 360.0;
+            } else if delta < -180.0 {
+                b += 360.0;
+            }
+        }
+        HueInterpolation::Longer => {
+            if delta > 0.0 && delta < 180.0 {
+                a += 360.0;
+            } else if delta > -180.0 && delta <= 0.0 {
+                b += 360.0;
+            }
+        }
+        HueInterpolation::Increasing => {
+            if b < a {
+                b += 360.0;
+            }
+        }
+        HueInterpolation::Decreasing => {
+            if a < b {
+                a += 360.0;
+            }
+        }
+    }
+    lerp(a, b, t).rem_euclid(360.0)
+}
+
+/// Mix two translucent sRGB colors in the given space, moving `t` of the way
+/// from `a` to `b`.
+///
+/// Like CSS `color-mix()`, channels are premultiplied by alpha before
+/// interpolating (except hue, which has no meaningful premultiplied form).
+/// A fully transparent color therefore contributes nothing but its alpha.
+#[must_use]
+pub fn mix_srgba(
+    (a, a_alpha): Rgba,
+    (b, b_alpha): Rgba,
+    t: f64,
+    space: MixSpace,
+    hue: HueInterpolation,
+) -> Rgba {
+    let to_space = |rgb: [f64; 3]| match space {
+        MixSpace::Srgb => rgb,
+        MixSpace::LinearSrgb => rgb.map(srgb_to_linear),
+        MixSpace::Oklab => srgb_to_oklab(rgb),
+        MixSpace::Oklch => oklab_to_oklch(srgb_to_oklab(rgb)),
+    };
+    let (mut a, mut b) = (to_space(a), to_space(b));
+    let premultiplied = if space == MixSpace::Oklch { 0..2 } else { 0..3 };
+
+    if space == MixSpace::Oklch {
+        // an achromatic color has no meaningful hue, so take the other one's.
+        match (a[1] < ACHROMATIC_THRESHOLD, b[1] < ACHROMATIC_THRESHOLD) {
+            (true, false) => a[2] = b[2],
+            (false, true) => b[2] = a[2],
+            _ => {}
+        }
+    }
+
+    let alpha = lerp(a_alpha, b_alpha, t);
+    let mut mixed = [0, 1, 2].map(|i| lerp(a[i], b[i], t));
+    if alpha > 0.0 {
+        for i in premultiplied {
+            mixed[i] = lerp(a[i] * a_alpha, b[i] * b_alpha, t) / alpha;
+        }
+    }
+
+    let rgb = match space {
+        MixSpace::Srgb => mixed,
+        MixSpace::LinearSrgb => mixed.map(linear_to_srgb),
+        MixSpace::Oklab => oklab_to_srgb(mixed),
+        MixSpace::Oklch => {
+            let [l, c, _] = mixed;
+            oklch_to_srgb_gamut_mapped([l, c, lerp_hue(a[2], b[2], t, hue)])
+        }
+    };
+    (rgb.map(|c| c.clamp(0.0, 1.0)), alpha)
+}
+
+/// A blend mode from the W3C Compositing and Blending spec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+/// The luminosity of an sRGB color, as defined for the non-separable blend modes.
+fn blend_lum([r, g, b]: [f64; 3]) -> f64 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn blend_set_lum(rgb: [f64; 3], lum: f64) -> [f64; 3] {
+    let d = lum - blend_lum(rgb);
+    let rgb = rgb.map(|c| c + d);
+    let l = blend_lum(rgb);
+    let min = rgb[0].min(rgb[1]).min(rgb[2]);
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    rgb.map(|c| {
+        if min < 0.0 {
+            l + (c - l) * l / (l - min)
+        } else if max > 1.0 {
+            l + (c - l) * (1.0 - l) / (max - l)
+        } else {
+            c
+        }
+    })
+}
+
+fn blend_sat(rgb: [f64; 3]) -> f64 {
+    rgb[0].max(rgb[1]).max(rgb[2]) - rgb[0].min(rgb[1]).min(rgb[2])
+}
+
+fn blend_set_sat(rgb: [f64; 3], sat: f64) -> [f64; 3] {
+    let min = rgb[0].min(rgb[1]).min(rgb[2]);
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    rgb.map(|c| {
+        if max > min {
+            (c - min) * sat / (max - min)
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Apply a blend mode to a backdrop and source sRGB color, before compositing.
+#[must_use]
+pub fn blend(backdrop: [f64; 3], source: [f64; 3], mode: BlendMode) -> [f64; 3] {
+    let separable = |f: fn(f64, f64) -> f64| [0, 1, 2].map(|i| f(backdrop[i], source[i]));
+    let hard_light = |b: f64, s: f64| {
+        if s <= 0.5 {
+            b * 2.0 * s
+        } else {
+            let s = 2.0 * s - 1.0;
+            b + s - b * s
+        }
+    };
+    match mode {
+        BlendMode::Normal => source,
+        BlendMode::Multiply => separable(|b, s| b * s),
+        BlendMode::Screen => separable(|b, s| b + s - b * s),
+        BlendMode::Overlay => [0, 1, 2].map(|i| hard_light(source[i], backdrop[i])),
+        BlendMode::Darken => separable(f64::min),
+        BlendMode::Lighten => separable(f64::max),
+        BlendMode::ColorDodge => separable(|b, s| {
+            if b == 0.0 {
+                0.0
+            } else if s >= 1.0 {
+                1.0
+            } else {
+                (b / (1.0 - s)).min(1.0)
+            }
+        }),
+        BlendMode::ColorBurn => separable(|b, s| {
+            if b >= 1.0 {
+                1.0
+            } else if s == 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - b) / s).min(1.0)
+            }
+        }),
+        BlendMode::HardLight => [0, 1, 2].map(|i| hard_light(backdrop[i], source[i])),
+        BlendMode::SoftLight => separable(|b, s| {
+            if s <= 0.5 {
+                b - (1.0 - 2.0 * s) * b * (1.0 - b)
+            } else {
+                let d = if b <= 0.25 {
+                    ((16.0 * b - 12.0) * b + 4.0) * b
+                } else {
+                    b.sqrt()
+                };
+                b + (2.0 * s - 1.0) * (d - b)
+            }
+        }),
+        BlendMode::Difference => separable(|b, s| (b - s).abs()),
+        BlendMode::Exclusion => separable(|b, s| b + s - 2.0 * b * s),
+        BlendMode::Hue => blend_set_lum(
+            blend_set_sat(source, blend_sat(backdrop)),
+            blend_lum(backdrop),
+        ),
+        BlendMode::Saturation => blend_set_lum(
+            blend_set_sat(backdrop, blend_sat(source)),
+            blend_lum(backdrop),
+        ),
+        BlendMode::Color => blend_set_lum(source, blend_lum(backdrop)),
+        BlendMode::Luminosity => blend_set_lum(backdrop, blend_lum(source)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f64; 3], b: [f64; 3]) {
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 1e-4, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn white_is_oklab_unit_lightness() {
+        assert_close(srgb_to_oklab([1.0, 1.0, 1.0]), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn oklab_matches_reference() {
+        // reference values from https://bottosson.github.io/posts/oklab/
+        assert_close(
+            linear_srgb_to_oklab([1.0, 0.0, 0.0]),
+            [0.627_955, 0.224_863, 0.125_846],
+        );
+    }
+
+    #[test]
+    fn oklab_round_trips() {
+        let rgb = [0.8, 0.3, 0.1];
+        assert_close(oklab_to_srgb(srgb_to_oklab(rgb)), rgb);
+    }
+
+    #[test]
+    fn gamut_mapping_keeps_lightness() {
+        let rgb = oklch_to_srgb_gamut_mapped([0.7, 0.5, 30.0]);
+        assert!(rgb.iter().all(|c| (0.0..=1.0).contains(c)));
+        assert!((srgb_to_oklab(rgb)[0] - 0.7).abs() < 1e-3);
+    }
+
+    #[test]
+    fn black_on_white_is_max_contrast() {
+        let white = relative_luminance([1.0, 1.0, 1.0]);
+        let black = relative_luminance([0.0, 0.0, 0.0]);
+        assert!((contrast_ratio(white, black) - 21.0).abs() < 1e-9);
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cvd_simulation_preserves_grays() {
+        for cvd in [Cvd::Protanopia, Cvd::Deuteranopia, Cvd::Tritanopia] {
+            assert_close(simulate_cvd([0.5, 0.5, 0.5], cvd, 1.0), [0.5, 0.5, 0.5]);
+        }
+    }
+
+    #[test]
+    fn hue_interpolation_directions() {
+        let mid = |method| lerp_hue(350.0, 30.0, 0.5, method);
+        assert!((mid(HueInterpolation::Shorter) - 10.0).abs() < 1e-9);
+        assert!((mid(HueInterpolation::Longer) - 190.0).abs() < 1e-9);
+        assert!((mid(HueInterpolation::Increasing) - 10.0).abs() < 1e-9);
+        assert!((mid(HueInterpolation::Decreasing) - 190.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hsl_round_trips() {
+        let rgb = [0.823_529, 0.058_824, 0.223_529];
+        assert_close(hsl_to_srgb(srgb_to_hsl(rgb)), rgb);
+        assert_close(srgb_to_hsl([0.5, 0.5, 0.5]), [0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn mixing_premultiplies_alpha() {
+        let red = ([1.0, 0.0, 0.0], 1.0);
+        let clear_blue = ([0.0, 0.0, 1.0], 0.0);
+        // hue isn't premultiplied, so oklch is left out here.
+        for space in [MixSpace::Srgb, MixSpace::LinearSrgb, MixSpace::Oklab] {
+            let (rgb, alpha) = mix_srgba(red, clear_blue, 0.5, space, HueInterpolation::Shorter);
+            assert_close(rgb, [1.0, 0.0, 0.0]);
+            assert!((alpha - 0.5).abs() < 1e-9);
+        }
+
+        let (rgb, alpha) = mix_srgba(
+            ([1.0, 0.0, 0.0], 0.5),
+            ([0.0, 0.0, 1.0], 1.0),
+            0.5,
+            MixSpace::Srgb,
+            HueInterpolation::Shorter,
+        );
+        assert_close(rgb, [1.0 / 3.0, 0.0, 2.0 / 3.0]);
+        assert!((alpha - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ciede2000_matches_reference() {
+        // pairs from Sharma, Wu and Dalal's CIEDE2000 test data
+        let pairs = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 2.5, 0.0], [50.0, 0.0, -2.5], 4.3065),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            ([2.0776, 0.0795, -1.135], [0.9033, -0.0636, -0.5514], 0.9082),
+        ];
+        for (a, b, expected) in pairs {
+            assert!((ciede2000(a, b) - expected).abs() < 1e-4);
+            assert!((ciede2000(b, a) - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn identical_colors_have_no_difference() {
+        let rgb = [0.8, 0.3, 0.1];
+        for method in [DeltaE::Cie76, DeltaE::Cie94, DeltaE::Ciede2000, DeltaE::Ok] {
+            assert!(delta_e(rgb, rgb, method).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn blend_modes_match_spec() {
+        let b = [0.2, 0.5, 0.8];
+        let s = [0.6, 0.4, 0.2];
+        assert_close(blend(b, s, BlendMode::Multiply), [0.12, 0.2, 0.16]);
+        assert_close(blend(b, s, BlendMode::Screen), [0.68, 0.7, 0.84]);
+        assert_close(blend(b, s, BlendMode::Overlay), [0.24, 0.4, 0.68]);
+        assert_close(blend(b, s, BlendMode::Difference), [0.4, 0.1, 0.6]);
+        // white and black are identities for multiply and screen respectively
+        assert_close(blend(b, [1.0; 3], BlendMode::Multiply), b);
+        assert_close(blend(b, [0.0; 3], BlendMode::Screen), b);
+        // luminosity keeps the backdrop's hue and the source's luminosity
+        let lum = blend(b, s, BlendMode::Luminosity);
+        assert!((blend_lum(lum) - blend_lum(s)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn oklch_round_trips() {
+        let lab = [0.5, -0.1, 0.05];
+        assert_close(oklch_to_oklab(oklab_to_oklch(lab)), lab);
+    }
+}
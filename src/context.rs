@@ -26,7 +26,7 @@ mod tests {
 
     #[test]
     fn test_merge_values() {
-        let a = tera::to_value(&json!({
+        let a = tera::to_value(json!({
             "a": 1,
             "b": {
                 "c": 2,
@@ -34,7 +34,7 @@ mod tests {
             },
         }))
         .expect("test value is always valid");
-        let b = tera::to_value(&json!({
+        let b = tera::to_value(json!({
             "b": {
                 "c": 4,
                 "e": 5,
@@ -45,7 +45,7 @@ mod tests {
         let result = merge_values(&a, &b);
         assert_eq!(
             result,
-            tera::to_value(&json!({
+            tera::to_value(json!({
                 "a": 1,
                 "b": {
                     "c": 4,
@@ -5,72 +5,459 @@ use std::{
 
 use base64::Engine as _;
 
-use crate::models::Color;
+use crate::{
+    colorspace::Cvd,
+    models::{Color, HexAlpha, HexFormat},
+    templating::reject_unknown_args,
+    terminal::{Ansi16Subset, Xterm256Subset},
+};
+
+/// The color space in which `add`, `sub`, and `mod` make their adjustments.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AdjustmentSpace {
+    Hsl,
+    Oklch,
+}
+
+fn adjustment_space(args: &HashMap<String, tera::Value>) -> Result<AdjustmentSpace, tera::Error> {
+    match args.get("space").map(tera::Value::as_str) {
+        None | Some(Some("hsl")) => Ok(AdjustmentSpace::Hsl),
+        Some(Some("oklch")) => Ok(AdjustmentSpace::Oklch),
+        Some(_) => Err(tera::Error::msg("space must be one of: hsl, oklch")),
+    }
+}
+
+/// Which of `mod`, `add`, or `sub` is being applied.
+#[derive(Clone, Copy)]
+enum Operation {
+    Mod,
+    Add,
+    Sub,
+}
+
+impl Operation {
+    fn pick<T>(self, modify: T, add: T, sub: T) -> T {
+        match self {
+            Self::Mod => modify,
+            Self::Add => add,
+            Self::Sub => sub,
+        }
+    }
+}
+
+/// Arguments accepted by `add`, `sub`, and `mod`.
+const ADJUSTMENT_ARGS: [&str; 6] = [
+    "hue",
+    "saturation",
+    "chroma",
+    "lightness",
+    "opacity",
+    "space",
+];
+
+/// Parse a proportion from 0 to 1.
+///
+/// Numbers are always fractions, so `0.2` is 20%. Percentages must be written
+/// as strings such as `"20%"`.
+fn fraction(name: &str, value: &tera::Value) -> Result<f64, tera::Error> {
+    let fraction = match value {
+        tera::Value::Number(n) => n.as_f64(),
+        tera::Value::String(s) => s
+            .strip_suffix('%')
+            .and_then(|p| p.trim().parse::<f64>().ok())
+            .map(|p| p / 100.0),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        tera::Error::msg(format!(
+            "{name} must be a number or a percentage string like \"50%\", got {value}"
+        ))
+    })?;
+
+    if !(0.0..=1.0).contains(&fraction) {
+        // numbers used to be percentages, so point those at the new forms
+        let hint = match value {
+            tera::Value::Number(_) if (1.0..=100.0).contains(&fraction) => {
+                format!("; use \"{fraction}%\" or {} instead", fraction / 100.0)
+            }
+            _ => String::new(),
+        };
+        return Err(tera::Error::msg(format!(
+            "{name} must be between 0 and 1 (or 0% and 100%), got {value}{hint}"
+        )));
+    }
+    Ok(fraction)
+}
+
+/// Parse a hue angle in degrees.
+fn degrees(name: &str, value: &tera::Value) -> Result<f64, tera::Error> {
+    value
+        .as_f64()
+        .filter(|d| d.is_finite())
+        .ok_or_else(|| tera::Error::msg(format!("{name} must be a number of degrees, got {value}")))
+}
+
+/// Parse an Oklch chroma, which is an absolute amount rather than a proportion.
+fn chroma(value: &tera::Value) -> Result<f64, tera::Error> {
+    value
+        .as_f64()
+        .filter(|c| (0.0..=0.5).contains(c))
+        .ok_or_else(|| {
+            tera::Error::msg(format!(
+                "chroma must be a number from 0 to 0.5, got {value}"
+            ))
+        })
+}
+
+/// Apply every adjustment given in `args` to the color.
+///
+/// Adjustments are applied in a fixed order regardless of how they were
+/// written: hue, then saturation or chroma, then lightness, then opacity.
+fn adjust(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+    op: Operation,
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &ADJUSTMENT_ARGS)?;
+
+    let mut color: Color = tera::from_value(value.clone())?;
+    let space = adjustment_space(args)?;
+
+    if let Some(hue) = args.get("hue") {
+        color = match space {
+            AdjustmentSpace::Hsl => {
+                let f: fn(&Color, f64) -> Color =
+                    op.pick(Color::mod_hue, Color::add_hue, Color::sub_hue);
+                f(&color, degrees("hue", hue)?)
+            }
+            AdjustmentSpace::Oklch => {
+                let f: fn(&Color, f64) -> Color = op.pick(
+                    Color::mod_oklch_hue,
+                    Color::add_oklch_hue,
+                    Color::sub_oklch_hue,
+                );
+                f(&color, degrees("hue", hue)?)
+            }
+        };
+    }
+
+    if let Some(saturation) = args.get("saturation") {
+        if space != AdjustmentSpace::Hsl {
+            return Err(tera::Error::msg(
+                "saturation is only available in hsl space, use chroma instead",
+            ));
+        }
+        let f: fn(&Color, f64) -> Color = op.pick(
+            Color::mod_saturation,
+            Color::add_saturation,
+            Color::sub_saturation,
+        );
+        color = f(&color, fraction("saturation", saturation)?);
+    }
+
+    if let Some(chroma_value) = args.get("chroma") {
+        if space != AdjustmentSpace::Oklch {
+            return Err(tera::Error::msg(
+                "chroma is only available in oklch space, use saturation instead",
+            ));
+        }
+        let f: fn(&Color, f64) -> Color = op.pick(
+            Color::mod_oklch_chroma,
+            Color::add_oklch_chroma,
+            Color::sub_oklch_chroma,
+        );
+        color = f(&color, chroma(chroma_value)?);
+    }
+
+    if let Some(lightness) = args.get("lightness") {
+        color = match space {
+            AdjustmentSpace::Hsl => {
+                let f: fn(&Color, f64) -> Color = op.pick(
+                    Color::mod_lightness,
+                    Color::add_lightness,
+                    Color::sub_lightness,
+                );
+                f(&color, fraction("lightness", lightness)?)
+            }
+            AdjustmentSpace::Oklch => {
+                let f: fn(&Color, f64) -> Color = op.pick(
+                    Color::mod_oklch_lightness,
+                    Color::add_oklch_lightness,
+                    Color::sub_oklch_lightness,
+                );
+                f(&color, fraction("lightness", lightness)?)
+            }
+        };
+    }
+
+    if let Some(opacity) = args.get("opacity") {
+        let f: fn(&Color, f64) -> Color =
+            op.pick(Color::mod_opacity, Color::add_opacity, Color::sub_opacity);
+        color = f(&color, fraction("opacity", opacity)?);
+    }
+
+    Ok(color)
+}
 
 pub fn modify(
     value: &tera::Value,
     args: &HashMap<String, tera::Value>,
-) -> Result<tera::Value, tera::Error> {
+) -> Result<Color, tera::Error> {
+    adjust(value, args, Operation::Mod)
+}
+
+pub fn add(value: &tera::Value, args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    adjust(value, args, Operation::Add)
+}
+
+pub fn sub(value: &tera::Value, args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    adjust(value, args, Operation::Sub)
+}
+
+pub fn ensure_contrast(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["against", "ratio"])?;
     let color: Color = tera::from_value(value.clone())?;
-    if let Some(hue) = args.get("hue") {
-        let hue = tera::from_value(hue.clone())?;
-        Ok(tera::to_value(color.mod_hue(hue))?)
-    } else if let Some(saturation) = args.get("saturation") {
-        let saturation = tera::from_value(saturation.clone())?;
-        Ok(tera::to_value(color.mod_saturation(saturation))?)
-    } else if let Some(lightness) = args.get("lightness") {
-        let lightness = tera::from_value(lightness.clone())?;
-        Ok(tera::to_value(color.mod_lightness(lightness))?)
-    } else if let Some(opacity) = args.get("opacity") {
-        let opacity = tera::from_value(opacity.clone())?;
-        Ok(tera::to_value(color.mod_opacity(opacity))?)
-    } else {
-        Ok(value.clone())
+    let against: Color = tera::from_value(
+        args.get("against")
+            .ok_or_else(|| tera::Error::msg("against color is required"))?
+            .clone(),
+    )?;
+    let ratio = args
+        .get("ratio")
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| tera::Error::msg("ratio must be a number"))
+        })
+        .transpose()?
+        .unwrap_or(4.5);
+    if !(1.0..=21.0).contains(&ratio) {
+        return Err(tera::Error::msg("ratio must be between 1 and 21"));
     }
+
+    let result = color.ensure_contrast(&against, ratio).ok_or_else(|| {
+        tera::Error::msg(format!(
+            "{} cannot reach a contrast ratio of {ratio} against {}",
+            color.identifier, against.identifier
+        ))
+    })?;
+    Ok(result)
 }
 
-pub fn add(
+pub fn simulate(
     value: &tera::Value,
     args: &HashMap<String, tera::Value>,
-) -> Result<tera::Value, tera::Error> {
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["cvd", "severity"])?;
     let color: Color = tera::from_value(value.clone())?;
-    if let Some(hue) = args.get("hue") {
-        let hue = tera::from_value(hue.clone())?;
-        Ok(tera::to_value(color.add_hue(hue))?)
-    } else if let Some(saturation) = args.get("saturation") {
-        let saturation = tera::from_value(saturation.clone())?;
-        Ok(tera::to_value(color.add_saturation(saturation))?)
-    } else if let Some(lightness) = args.get("lightness") {
-        let lightness = tera::from_value(lightness.clone())?;
-        Ok(tera::to_value(color.add_lightness(lightness))?)
-    } else if let Some(opacity) = args.get("opacity") {
-        let opacity = tera::from_value(opacity.clone())?;
-        Ok(tera::to_value(color.add_opacity(opacity))?)
-    } else {
-        Ok(value.clone())
+    let cvd: Cvd = tera::from_value(
+        args.get("cvd")
+            .ok_or_else(|| tera::Error::msg("cvd is required"))?
+            .clone(),
+    )
+    .map_err(|_| {
+        tera::Error::msg("cvd must be one of: none, protanopia, deuteranopia, tritanopia")
+    })?;
+    let severity = args
+        .get("severity")
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| tera::Error::msg("severity must be a number"))
+        })
+        .transpose()?
+        .unwrap_or(1.0);
+    if !(0.0..=1.0).contains(&severity) {
+        return Err(tera::Error::msg("severity must be between 0 and 1"));
     }
+
+    Ok(color.simulate(cvd, severity))
+}
+
+pub fn flatten(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["over"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    let over: Color = tera::from_value(
+        args.get("over")
+            .ok_or_else(|| tera::Error::msg("over color is required"))?
+            .clone(),
+    )?;
+    Ok(color.flatten(&over))
+}
+
+pub fn invert(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &[])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(color.invert())
+}
+
+/// Rotate the hue by 180 degrees, in HSL or Oklch, keeping lightness.
+pub fn complement(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["space"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(match adjustment_space(args)? {
+        AdjustmentSpace::Hsl => color.add_hue(180.0),
+        AdjustmentSpace::Oklch => color.add_oklch_hue(180.0),
+    })
 }
 
-pub fn sub(
+pub fn grayscale(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &[])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(color.grayscale())
+}
+
+pub fn luminance(
     value: &tera::Value,
     args: &HashMap<String, tera::Value>,
 ) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &[])?;
     let color: Color = tera::from_value(value.clone())?;
-    if let Some(hue) = args.get("hue") {
-        let hue = tera::from_value(hue.clone())?;
-        Ok(tera::to_value(color.sub_hue(hue))?)
-    } else if let Some(saturation) = args.get("saturation") {
-        let saturation = tera::from_value(saturation.clone())?;
-        Ok(tera::to_value(color.sub_saturation(saturation))?)
-    } else if let Some(lightness) = args.get("lightness") {
-        let lightness = tera::from_value(lightness.clone())?;
-        Ok(tera::to_value(color.sub_lightness(lightness))?)
-    } else if let Some(opacity) = args.get("opacity") {
-        let opacity = tera::from_value(opacity.clone())?;
-        Ok(tera::to_value(color.sub_opacity(opacity))?)
-    } else {
-        Ok(value.clone())
-    }
+    Ok(tera::to_value(color.luminance())?)
+}
+
+/// Format a color as hex, following the template's hex options unless
+/// overridden by the `alpha` argument.
+pub fn hex(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+    hex_format: &HexFormat,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["alpha"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    let alpha: Option<HexAlpha> = args
+        .get("alpha")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()
+        .map_err(|_| {
+            tera::Error::msg("alpha must be one of: suffix, prefix, always, always-prefix, never")
+        })?;
+    let format = HexFormat {
+        alpha: alpha.unwrap_or(hex_format.alpha),
+        ..hex_format.clone()
+    };
+    Ok(tera::to_value(color.hex_with(&format))?)
+}
+
+/// Parse the optional `alpha` argument of the numeric format filters.
+fn alpha_arg(args: &HashMap<String, tera::Value>) -> Result<bool, tera::Error> {
+    args.get("alpha")
+        .map(|v| {
+            v.as_bool()
+                .ok_or_else(|| tera::Error::msg("alpha must be a boolean"))
+        })
+        .transpose()
+        .map(|alpha| alpha.unwrap_or(false))
+}
+
+pub fn css_rgb(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &[])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_rgb())?)
+}
+
+pub fn css_hsl(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &[])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_hsl())?)
+}
+
+pub fn css_oklch(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &[])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.css_oklch())?)
+}
+
+pub fn rgb_int(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["alpha"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.rgb_int(alpha_arg(args)?))?)
+}
+
+pub fn bgr_int(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &[])?;
+    let color: Color = tera::from_value(value.clone())?;
+    Ok(tera::to_value(color.bgr_int())?)
+}
+
+pub fn floats(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["precision", "alpha"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    let precision = args
+        .get("precision")
+        .map(|v| {
+            v.as_u64()
+                .filter(|p| *p <= 15)
+                .ok_or_else(|| tera::Error::msg("precision must be an integer from 0 to 15"))
+        })
+        .transpose()?
+        .unwrap_or(3);
+    Ok(tera::to_value(
+        color.floats(precision as i32, alpha_arg(args)?),
+    )?)
+}
+
+pub fn xterm256(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["subset"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    let subset: Xterm256Subset = args
+        .get("subset")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()
+        .map_err(|_| {
+            tera::Error::msg("subset must be one of: all, ansi, cube, grayscale, extended")
+        })?
+        .unwrap_or_default();
+    Ok(tera::to_value(color.xterm256(subset))?)
+}
+
+pub fn ansi16(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["subset"])?;
+    let color: Color = tera::from_value(value.clone())?;
+    let subset: Ansi16Subset = args
+        .get("subset")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()
+        .map_err(|_| tera::Error::msg("subset must be one of: all, normal, bright"))?
+        .unwrap_or_default();
+    Ok(tera::to_value(color.ansi16(subset))?)
 }
 
 pub fn urlencode_lzma(
@@ -1,8 +1,42 @@
 use std::collections::{BTreeMap, HashMap};
 
-use crate::models::Color;
+use crate::{
+    colorspace::{BlendMode, DeltaE, HueInterpolation, MixSpace},
+    models::{Color, Flavor},
+    parse,
+    templating::reject_unknown_args,
+};
+
+/// Parse the optional `space` and `hue` arguments shared by mixing functions.
+fn mix_options(
+    args: &HashMap<String, tera::Value>,
+) -> Result<(MixSpace, HueInterpolation), tera::Error> {
+    let space: MixSpace = args
+        .get("space")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()
+        .map_err(|_| tera::Error::msg("space must be one of: srgb, linear-srgb, oklab, oklch"))?
+        .unwrap_or_default();
+    let hue: HueInterpolation = args
+        .get("hue")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()
+        .map_err(|_| {
+            tera::Error::msg("hue must be one of: shorter, longer, increasing, decreasing")
+        })?
+        .unwrap_or_default();
+    if args.contains_key("hue") && space != MixSpace::Oklch {
+        return Err(tera::Error::msg(
+            "hue only applies when mixing in oklch space",
+        ));
+    }
+
+    Ok((space, hue))
+}
+
+pub fn mix(args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["base", "blend", "amount", "space", "hue"])?;
 
-pub fn mix(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
     let base: Color = tera::from_value(
         args.get("base")
             .ok_or_else(|| tera::Error::msg("base color is required"))?
@@ -19,9 +53,232 @@ pub fn mix(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Err
         .as_f64()
         .ok_or_else(|| tera::Error::msg("amount must be a number"))?;
 
-    let result = Color::mix(&base, &blend, amount);
+    let (space, hue) = mix_options(args)?;
+
+    Ok(Color::mix_in(&base, &blend, amount, space, hue))
+}
+
+pub fn gradient(args: &HashMap<String, tera::Value>) -> Result<Vec<Color>, tera::Error> {
+    reject_unknown_args(args, &["from", "to", "steps", "space", "hue"])?;
+
+    let from: Color = tera::from_value(
+        args.get("from")
+            .ok_or_else(|| tera::Error::msg("from color is required"))?
+            .clone(),
+    )?;
+    let to: Color = tera::from_value(
+        args.get("to")
+            .ok_or_else(|| tera::Error::msg("to color is required"))?
+            .clone(),
+    )?;
+    let steps = args
+        .get("steps")
+        .ok_or_else(|| tera::Error::msg("steps is required"))?
+        .as_u64()
+        .and_then(|s| u32::try_from(s).ok())
+        .ok_or_else(|| tera::Error::msg("steps must be a positive integer"))?;
+    if steps < 2 {
+        return Err(tera::Error::msg("steps must be at least 2"));
+    }
+    let (space, hue) = mix_options(args)?;
+
+    // `mix` takes the proportion of the base color, so walk it from 1 down to 0.
+    let last = f64::from(steps - 1);
+    Ok((0..steps)
+        .map(|i| Color::mix_in(&from, &to, 1.0 - f64::from(i) / last, space, hue))
+        .collect())
+}
+
+/// Blend `top` onto `base` with a W3C blend mode.
+pub fn blend(args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["base", "top", "mode", "opacity"])?;
+
+    let base: Color = tera::from_value(
+        args.get("base")
+            .ok_or_else(|| tera::Error::msg("base color is required"))?
+            .clone(),
+    )?;
+    let top: Color = tera::from_value(
+        args.get("top")
+            .ok_or_else(|| tera::Error::msg("top color is required"))?
+            .clone(),
+    )?;
+    let mode: BlendMode = args
+        .get("mode")
+        .map(|v| tera::from_value(v.clone()))
+        .transpose()
+        .map_err(|_| {
+            tera::Error::msg(
+                "mode must be one of: normal, multiply, screen, overlay, darken, lighten, \
+                 color-dodge, color-burn, hard-light, soft-light, difference, exclusion, \
+                 hue, saturation, color, luminosity",
+            )
+        })?
+        .unwrap_or_default();
+    let opacity = args
+        .get("opacity")
+        .map(|v| {
+            v.as_f64()
+                .filter(|o| (0.0..=1.0).contains(o))
+                .ok_or_else(|| tera::Error::msg("opacity must be a number from 0 to 1"))
+        })
+        .transpose()?
+        .unwrap_or(1.0);
+
+    Ok(base.blend(&top, mode, opacity))
+}
+
+pub fn contrast(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["a", "b"])?;
+
+    let a: Color = tera::from_value(
+        args.get("a")
+            .ok_or_else(|| tera::Error::msg("a color is required"))?
+            .clone(),
+    )?;
+    let b: Color = tera::from_value(
+        args.get("b")
+            .ok_or_else(|| tera::Error::msg("b color is required"))?
+            .clone(),
+    )?;
+
+    Ok(tera::to_value(a.contrast(&b))?)
+}
+
+/// Parse the optional `method` argument for color difference functions.
+///
+/// The method may be given as a string or a number, so both `method="2000"`
+/// and `method=2000` work.
+fn delta_e_method(args: &HashMap<String, tera::Value>) -> Result<DeltaE, tera::Error> {
+    let Some(method) = args.get("method") else {
+        return Ok(DeltaE::default());
+    };
+    let method = match method {
+        tera::Value::Number(n) => tera::Value::String(n.to_string()),
+        other => other.clone(),
+    };
+    tera::from_value(method)
+        .map_err(|_| tera::Error::msg("method must be one of: 76, 94, 2000, ok"))
+}
+
+pub fn delta_e(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
+    reject_unknown_args(args, &["a", "b", "method"])?;
+
+    let a: Color = tera::from_value(
+        args.get("a")
+            .ok_or_else(|| tera::Error::msg("a color is required"))?
+            .clone(),
+    )?;
+    let b: Color = tera::from_value(
+        args.get("b")
+            .ok_or_else(|| tera::Error::msg("b color is required"))?
+            .clone(),
+    )?;
+    let method = delta_e_method(args)?;
+
+    Ok(tera::to_value(a.delta_e(&b, method))?)
+}
+
+/// Find the color in a flavor that is perceptually closest to `value`.
+pub fn nearest(args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["value", "flavor", "method"])?;
+
+    let value: Color = tera::from_value(
+        args.get("value")
+            .ok_or_else(|| tera::Error::msg("value color is required"))?
+            .clone(),
+    )?;
+    // the function can't see the palette, so it needs the flavor itself
+    let flavor: Flavor = match args
+        .get("flavor")
+        .ok_or_else(|| tera::Error::msg("flavor is required"))?
+    {
+        tera::Value::String(identifier) => {
+            return Err(tera::Error::msg(format!(
+                "flavor must be a flavor object, not a string (use `flavors.{identifier}` instead of \"{identifier}\")"
+            )))
+        }
+        flavor => tera::from_value(flavor.clone()).map_err(|_| {
+            tera::Error::msg("flavor must be a flavor object, such as `flavor` or `flavors.mocha`")
+        })?,
+    };
+    let method = delta_e_method(args)?;
+
+    flavor
+        .nearest(&value, method)
+        .cloned()
+        .ok_or_else(|| tera::Error::msg("flavor has no colors"))
+}
+
+/// Pick the first candidate that meets the minimum contrast ratio against
+/// `bg`. If none do, this is an error unless `fallback` is set, in which case
+/// the candidate with the highest contrast is returned.
+pub fn readable(args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["bg", "candidates", "min", "fallback"])?;
+
+    let bg: Color = tera::from_value(
+        args.get("bg")
+            .ok_or_else(|| tera::Error::msg("bg color is required"))?
+            .clone(),
+    )?;
+    let candidates: Vec<Color> = tera::from_value(
+        args.get("candidates")
+            .ok_or_else(|| tera::Error::msg("candidates are required"))?
+            .clone(),
+    )?;
+    let min = args
+        .get("min")
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| tera::Error::msg("min must be a number"))
+        })
+        .transpose()?
+        .unwrap_or(4.5);
+    let fallback = args
+        .get("fallback")
+        .map(|v| {
+            v.as_bool()
+                .ok_or_else(|| tera::Error::msg("fallback must be a boolean"))
+        })
+        .transpose()?
+        .unwrap_or(false);
+
+    if let Some(result) = candidates.iter().find(|c| c.contrast(&bg) >= min) {
+        return Ok(result.clone());
+    }
+    let best = candidates
+        .iter()
+        .max_by(|a, b| a.contrast(&bg).total_cmp(&b.contrast(&bg)))
+        .ok_or_else(|| tera::Error::msg("candidates must not be empty"))?;
+    if !fallback {
+        return Err(tera::Error::msg(format!(
+            "no candidate has a contrast ratio of at least {min} against `{bg}`, the best is `{best}` at {ratio:.2} (pass fallback=true to use it anyway)",
+            bg = bg.identifier,
+            best = best.identifier,
+            ratio = best.contrast(&bg),
+        )));
+    }
+    Ok(best.clone())
+}
+
+/// Build a color from a hex or CSS color string.
+pub fn color(args: &HashMap<String, tera::Value>) -> Result<Color, tera::Error> {
+    reject_unknown_args(args, &["value"])?;
+
+    let value = args
+        .get("value")
+        .ok_or_else(|| tera::Error::msg("value is required"))?
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("value must be a string"))?;
+    let (rgb, alpha) = parse::color(value).map_err(|e| tera::Error::msg(e.to_string()))?;
 
-    Ok(tera::to_value(result)?)
+    Ok(Color::new(
+        value.to_string(),
+        value.to_string(),
+        false,
+        rgb,
+        alpha,
+    ))
 }
 
 pub fn if_fn(args: &HashMap<String, tera::Value>) -> Result<tera::Value, tera::Error> {
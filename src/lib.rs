@@ -1,9 +1,13 @@
+pub mod accessibility;
 pub mod cli;
+pub mod colorspace;
+pub mod context;
 pub mod filters;
 pub mod frontmatter;
 pub mod functions;
+pub mod markdown;
 pub mod matrix;
 pub mod models;
+pub mod parse;
 pub mod templating;
-pub mod context;
-pub mod markdown;
\ No newline at end of file
+pub mod terminal;
@@ -7,15 +7,17 @@ use std::{
 };
 
 use anyhow::{anyhow, Context as _};
-use catppuccin::FlavorName;
 use clap::Parser as _;
+use indexmap::IndexMap;
 use itertools::Itertools;
 use whiskers2::{
-    cli::{Args, OutputFormat},
+    accessibility,
+    cli::{Args, ColorOverrides, OutputFormat},
     context::merge_values,
     frontmatter, markdown,
     matrix::{self, Matrix},
-    models, templating,
+    models::{self, DerivedFlavor, HexAlpha, HexFormat},
+    templating,
 };
 
 const FRONTMATTER_OPTIONS_SECTION: &str = "whiskers";
@@ -23,46 +25,41 @@ const FRONTMATTER_OPTIONS_SECTION: &str = "whiskers";
 #[derive(Default, Debug, serde::Deserialize)]
 struct TemplateOptions {
     version: Option<semver::VersionReq>,
-    matrix: Option<Matrix>,
+    /// The matrix as written, before magic iterables are expanded with the palette.
+    matrix: Option<Vec<tera::Value>>,
     filename: Option<String>,
     hex_prefix: Option<String>,
     #[serde(default)]
     capitalize_hex: bool,
+    #[serde(default)]
+    hex_format: HexAlpha,
+    /// Extra flavors to add to the palette, keyed by identifier. These only
+    /// exist while rendering this template, not in `--accessibility-report`.
+    #[serde(default)]
+    derive: IndexMap<String, DerivedFlavor>,
+    /// Color overrides for the template, in the same form as `--color-overrides`.
+    ///
+    /// These are applied first, and `--color-overrides` on top of them, so
+    /// from lowest to highest precedence: frontmatter `all`, frontmatter
+    /// flavor, command-line `all`, command-line flavor.
+    color_overrides: Option<ColorOverrides>,
 }
 
 impl TemplateOptions {
-    fn from_frontmatter(
-        frontmatter: &HashMap<String, tera::Value>,
-        only_flavor: Option<FlavorName>,
-    ) -> anyhow::Result<Self> {
-        // a `TemplateOptions` object before matrix transformation
-        #[derive(serde::Deserialize)]
-        struct RawTemplateOptions {
-            version: Option<semver::VersionReq>,
-            matrix: Option<Vec<tera::Value>>,
-            filename: Option<String>,
-            hex_prefix: Option<String>,
-            #[serde(default)]
-            capitalize_hex: bool,
-        }
+    fn from_frontmatter(frontmatter: &HashMap<String, tera::Value>) -> anyhow::Result<Self> {
+        frontmatter.get(FRONTMATTER_OPTIONS_SECTION).map_or_else(
+            || Ok(Self::default()),
+            |opts| {
+                tera::from_value(opts.clone()).context("Frontmatter `whiskers` section is invalid")
+            },
+        )
+    }
 
-        if let Some(opts) = frontmatter.get(FRONTMATTER_OPTIONS_SECTION) {
-            let opts: RawTemplateOptions = tera::from_value(opts.clone())
-                .context("Frontmatter `whiskers` section is invalid")?;
-            let matrix = opts
-                .matrix
-                .map(|m| matrix::from_values(m, only_flavor))
-                .transpose()
-                .context("Frontmatter matrix is invalid")?;
-            Ok(Self {
-                version: opts.version,
-                matrix,
-                filename: opts.filename,
-                hex_prefix: opts.hex_prefix,
-                capitalize_hex: opts.capitalize_hex,
-            })
-        } else {
-            Ok(Self::default())
+    fn hex_format(&self) -> HexFormat {
+        HexFormat {
+            prefix: self.hex_prefix.clone(),
+            capitalize: self.capitalize_hex,
+            alpha: self.hex_format,
         }
     }
 }
@@ -76,6 +73,20 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
+    let base_palette = base_palette(&args)?;
+
+    if args.accessibility_report {
+        check_flavor(&base_palette, args.flavor.as_deref())?;
+        let palette = models::build_palette(
+            &base_palette,
+            &HexFormat::default(),
+            args.color_overrides.as_ref(),
+        )
+        .context("Palette context cannot be built")?;
+        accessibility_report(&palette, args.flavor.as_deref(), args.output_format);
+        return Ok(());
+    }
+
     let template = args
         .template
         .expect("args.template is guaranteed by clap to be set");
@@ -87,9 +98,8 @@ fn main() -> anyhow::Result<()> {
             .context("Template contents could not be read")?,
     )
     .context("Frontmatter is invalid")?;
-    let template_opts =
-        TemplateOptions::from_frontmatter(&doc.frontmatter, args.flavor.map(Into::into))
-            .context("Could not get template options from frontmatter")?;
+    let template_opts = TemplateOptions::from_frontmatter(&doc.frontmatter)
+        .context("Could not get template options from frontmatter")?;
 
     if !template_from_stdin && !template_is_compatible(&template_opts) {
         std::process::exit(1);
@@ -116,17 +126,21 @@ fn main() -> anyhow::Result<()> {
     }
 
     // build the Tera engine and palette
-    let mut tera = templating::make_engine();
+    let hex_format = template_opts.hex_format();
+    let mut tera = templating::make_engine(&hex_format);
     tera.add_raw_template(&template_name, &doc.body)
         .context("Template is invalid")?;
-    let palette = models::build_palette(
-        template_opts.capitalize_hex,
-        template_opts.hex_prefix.as_deref(),
+    let palette = template_palette(
+        &base_palette,
+        &template_opts,
+        &hex_format,
+        args.flavor.as_deref(),
         args.color_overrides.as_ref(),
-    )
-    .context("Palette context cannot be built")?;
+    )?;
 
     if let Some(matrix) = template_opts.matrix {
+        let matrix = matrix::from_values(matrix, &palette, args.flavor.as_deref())
+            .context("Frontmatter matrix is invalid")?;
         let Some(filename_template) = template_opts.filename else {
             anyhow::bail!("Filename template is required for multi-output render");
         };
@@ -149,7 +163,7 @@ fn main() -> anyhow::Result<()> {
             })
             .transpose()?;
         render_single_output(
-            args.flavor.map(Into::into),
+            args.flavor.as_deref(),
             &ctx,
             &palette,
             &tera,
@@ -162,6 +176,63 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Load the palette to build on, from `--palette` or the built-in one.
+fn base_palette(args: &Args) -> anyhow::Result<models::Palette> {
+    args.palette
+        .as_deref()
+        .map_or_else(|| Ok(models::default_palette()), models::load_palette)
+        .context("Palette file cannot be loaded")
+}
+
+/// Check that `--flavor` names one of the palette's flavors.
+fn check_flavor(palette: &models::Palette, flavor: Option<&str>) -> anyhow::Result<()> {
+    if let Some(flavor) = flavor {
+        if !palette.flavors.contains_key(flavor) {
+            anyhow::bail!(
+                "Unknown flavor `{flavor}`, expected one of: {}",
+                palette.flavors.keys().join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Build the palette for a template, with its derived flavors and color
+/// overrides.
+fn template_palette(
+    base_palette: &models::Palette,
+    template_opts: &TemplateOptions,
+    hex_format: &HexFormat,
+    flavor: Option<&str>,
+    color_overrides: Option<&ColorOverrides>,
+) -> anyhow::Result<models::Palette> {
+    // overrides for a flavor are applied before anything is derived from it,
+    // and overrides for derived flavors after they've been derived
+    let is_derived = |flavor: &str| template_opts.derive.contains_key(flavor);
+    let (frontmatter, frontmatter_derived) = template_opts
+        .color_overrides
+        .as_ref()
+        .map(|co| co.split_off(is_derived))
+        .unzip();
+    let (cli, cli_derived) = color_overrides.map(|co| co.split_off(is_derived)).unzip();
+
+    // frontmatter overrides are applied first, so that any from the command
+    // line take precedence over them
+    let palette = models::build_palette(base_palette, hex_format, frontmatter.as_ref())
+        .context("Frontmatter color overrides are invalid")?;
+    let palette = models::build_palette(&palette, hex_format, cli.as_ref())
+        .context("Palette context cannot be built")?;
+
+    let palette = models::derive_flavors(&palette, &template_opts.derive)
+        .context("Frontmatter derived flavors are invalid")?;
+    check_flavor(&palette, flavor)?;
+
+    let palette = models::build_palette(&palette, hex_format, frontmatter_derived.as_ref())
+        .context("Frontmatter color overrides are invalid")?;
+    models::build_palette(&palette, hex_format, cli_derived.as_ref())
+        .context("Palette context cannot be built")
+}
+
 #[allow(clippy::too_many_lines)]
 fn list_functions(format: OutputFormat) {
     match format {
@@ -194,6 +265,36 @@ fn list_functions(format: OutputFormat) {
     }
 }
 
+fn accessibility_report(palette: &models::Palette, flavor: Option<&str>, format: OutputFormat) {
+    let report = accessibility::build_report(palette, flavor);
+    match format {
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).expect("report is guaranteed to be valid")
+            );
+        }
+        OutputFormat::Yaml => {
+            println!(
+                "{}",
+                serde_yaml::to_string(&report).expect("report is guaranteed to be valid")
+            );
+        }
+        OutputFormat::Markdown => {
+            println!(
+                "{}",
+                markdown::format_accessibility_report(&report, palette, markdown::Format::List)
+            );
+        }
+        OutputFormat::MarkdownTable => {
+            println!(
+                "{}",
+                markdown::format_accessibility_report(&report, palette, markdown::Format::Table)
+            );
+        }
+    }
+}
+
 fn template_name(template: &clap_stdin::FileOrStdin) -> String {
     match &template.source {
         clap_stdin::Source::Stdin => "template".to_string(),
@@ -223,13 +324,13 @@ fn template_is_compatible(template_opts: &TemplateOptions) -> bool {
         eprintln!("    version: \"{whiskers_version}\"");
         eprintln!("---");
         eprintln!();
-    };
+    }
 
     true
 }
 
 fn render_single_output(
-    flavor: Option<FlavorName>,
+    flavor: Option<&str>,
     ctx: &tera::Context,
     palette: &models::Palette,
     tera: &tera::Tera,
@@ -239,7 +340,7 @@ fn render_single_output(
     let mut ctx = ctx.clone();
     ctx.insert("flavors", &palette.flavors);
     if let Some(flavor) = flavor {
-        let flavor = &palette.flavors[flavor.identifier()];
+        let flavor = &palette.flavors[flavor];
         ctx.insert("flavor", flavor);
 
         // also throw in the flavor's colors for convenience
@@ -261,8 +362,9 @@ fn render_single_output(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_multi_output(
-    matrix: HashMap<String, Vec<String>>,
+    matrix: Matrix,
     filename_template: &str,
     ctx: &tera::Context,
     palette: &models::Palette,
@@ -284,8 +386,10 @@ fn render_multi_output(
             // `{% set flavor = flavors[flavor] %}`
             // at the top of every template.
             if key == "flavor" {
-                let flavor: catppuccin::FlavorName = value.parse()?;
-                let flavor = &palette.flavors[flavor.identifier()];
+                let flavor = palette
+                    .flavors
+                    .get(&value)
+                    .ok_or_else(|| anyhow!("Unknown flavor `{value}` in matrix"))?;
                 ctx.insert("flavor", flavor);
             } else {
                 ctx.insert(key, &value);
@@ -298,10 +402,7 @@ fn render_multi_output(
             .context("Filename template render failed")?;
 
         if dry_run || cfg!(test) {
-            println!(
-                "Would write {} bytes into {filename}",
-                result.as_bytes().len()
-            );
+            println!("Would write {} bytes into {filename}", result.len());
         } else if check {
             check_result_with_file(&filename, &result).context("Check mode failed")?;
         } else {
@@ -342,7 +443,7 @@ where
     let mut actual_file = tempfile::NamedTempFile::new()?;
     write!(&mut actual_file, "{actual}")?;
     if let Ok(mut child) = process::Command::new(tool)
-        .args([actual_file.path(), &expected_path])
+        .args([actual_file.path(), expected_path])
         .spawn()
     {
         child.wait()?;
@@ -1,6 +1,9 @@
+use std::fmt::Write as _;
+
+use indexmap::IndexMap;
 use itertools::Itertools as _;
 
-use crate::templating;
+use crate::{accessibility, models::Palette, templating};
 
 #[derive(Clone, Copy)]
 pub enum Format {
@@ -16,20 +19,25 @@ pub fn format_filters_and_functions(format: Format) -> String {
     }
 }
 
+const EXAMPLE_FLAVOR_NOTE: &str = "Example outputs are rendered with the Latte flavor.\n\n";
+
 fn list_format() -> String {
     let mut result = String::new();
+    result.push_str(EXAMPLE_FLAVOR_NOTE);
     result.push_str("## Functions\n\n");
     for function in templating::all_functions() {
-        result.push_str(&format!(
+        let _ = write!(
+            result,
             "### `{name}`\n\n{description}\n\n",
             name = function.name,
             description = function.description
-        ));
+        );
         if !function.examples.is_empty() {
             result.push_str("#### Examples\n\n");
             for example in &function.examples {
-                result.push_str(&format!(
-                    "- `{name}({input})` => `{output}`\n",
+                let _ = writeln!(
+                    result,
+                    "- `{name}({input})` => `{output}`",
                     name = function.name,
                     input = example
                         .inputs
@@ -37,7 +45,7 @@ fn list_format() -> String {
                         .map(|(k, v)| format!("{k}={v}"))
                         .join(", "),
                     output = example.output
-                ));
+                );
             }
             result.push('\n');
         }
@@ -45,16 +53,18 @@ fn list_format() -> String {
 
     result.push_str("## Filters\n\n");
     for filter in templating::all_filters() {
-        result.push_str(&format!(
+        let _ = write!(
+            result,
             "### `{name}`\n\n{description}\n\n",
             name = filter.name,
             description = filter.description
-        ));
+        );
         if !filter.examples.is_empty() {
             result.push_str("#### Examples\n\n");
             for example in &filter.examples {
-                result.push_str(&format!(
-                    "- `{value} | {name}({input})` => `{output}`\n",
+                let _ = writeln!(
+                    result,
+                    "- `{value} | {name}({input})` => `{output}`",
                     value = example.value,
                     name = filter.name,
                     input = example
@@ -63,7 +73,7 @@ fn list_format() -> String {
                         .map(|(k, v)| format!("{k}={v}"))
                         .join(", "),
                     output = example.output
-                ));
+                );
             }
             result.push('\n');
         }
@@ -74,12 +84,14 @@ fn list_format() -> String {
 
 fn table_format() -> String {
     let mut result = String::new();
+    result.push_str(EXAMPLE_FLAVOR_NOTE);
     result.push_str("## Functions\n\n");
     result.push_str("| Name | Description | Examples |\n");
     result.push_str("|------|-------------|----------|\n");
     for function in templating::all_functions() {
-        result.push_str(&format!(
-            "| `{name}` | {description} | {examples} |\n",
+        let _ = writeln!(
+            result,
+            "| `{name}` | {description} | {examples} |",
             name = function.name,
             description = function.description,
             examples = if function.examples.is_empty() {
@@ -101,15 +113,16 @@ fn table_format() -> String {
                         )
                     })
             }
-        ));
+        );
     }
 
     result.push_str("## Filters\n\n");
     result.push_str("| Name | Description | Examples |\n");
     result.push_str("|------|-------------|----------|\n");
     for filter in templating::all_filters() {
-        result.push_str(&format!(
-            "| `{name}` | {description} | {examples} |\n",
+        let _ = writeln!(
+            result,
+            "| `{name}` | {description} | {examples} |",
             name = filter.name,
             description = filter.description,
             examples = if filter.examples.is_empty() {
@@ -129,8 +142,86 @@ fn table_format() -> String {
                     )
                 })
             }
-        ));
+        );
+    }
+
+    result
+}
+
+#[must_use]
+pub fn format_accessibility_report(
+    report: &accessibility::Report,
+    palette: &Palette,
+    format: Format,
+) -> String {
+    match format {
+        Format::List => report_matrix_format(report, palette),
+        Format::Table => report_table_format(report, palette),
+    }
+}
+
+fn report_matrix_format(report: &accessibility::Report, palette: &Palette) -> String {
+    let mut result = String::new();
+    for (identifier, pairs) in report {
+        let flavor = &palette.flavors[identifier];
+        let name = |id: &str| flavor.colors[id].name.as_str();
+
+        let mut rows: IndexMap<&str, Vec<&accessibility::Pair>> = IndexMap::new();
+        for pair in pairs {
+            rows.entry(pair.foreground.as_str()).or_default().push(pair);
+        }
+        let Some(first) = rows.values().next() else {
+            continue;
+        };
+
+        let _ = writeln!(result, "## {}\n", flavor.name);
+        let _ = writeln!(
+            result,
+            "| | {} |",
+            first.iter().map(|p| name(&p.background)).join(" | ")
+        );
+        let _ = writeln!(result, "|---|{}", "---|".repeat(first.len()));
+        for (foreground, pairs) in &rows {
+            let _ = writeln!(
+                result,
+                "| {} | {} |",
+                name(foreground),
+                pairs
+                    .iter()
+                    .map(|p| format!("{:.2} {}", p.ratio, p.level()))
+                    .join(" | ")
+            );
+        }
+        result.push('\n');
     }
+    result
+}
 
+fn report_table_format(report: &accessibility::Report, palette: &Palette) -> String {
+    let mut result = String::new();
+    result.push_str(
+        "| Flavor | Foreground | Background | Ratio | AA | AAA | AA Large | AAA Large |\n",
+    );
+    result.push_str(
+        "|--------|------------|------------|-------|----|-----|----------|-----------|\n",
+    );
+    let mark = |pass: bool| if pass { "Pass" } else { "Fail" };
+    for (identifier, pairs) in report {
+        let flavor = &palette.flavors[identifier];
+        for pair in pairs {
+            let _ = writeln!(
+                result,
+                "| {} | {} | {} | {:.2} | {} | {} | {} | {} |",
+                flavor.name,
+                flavor.colors[&pair.foreground].name,
+                flavor.colors[&pair.background].name,
+                pair.ratio,
+                mark(pair.aa),
+                mark(pair.aaa),
+                mark(pair.aa_large),
+                mark(pair.aaa_large),
+            );
+        }
+    }
     result
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use catppuccin::FlavorName;
+use crate::models::Palette;
 
 pub type Matrix = HashMap<String, Vec<String>>;
 
@@ -21,16 +21,17 @@ pub enum Error {
 // string array elements are substituted with the array from `iterables`.
 pub fn from_values(
     values: Vec<tera::Value>,
-    only_flavor: Option<FlavorName>,
+    palette: &Palette,
+    only_flavor: Option<&str>,
 ) -> Result<Matrix, Error> {
-    let iterables = magic_iterables(only_flavor);
+    let iterables = magic_iterables(palette, only_flavor);
     values
         .into_iter()
         .map(|v| match v {
             tera::Value::String(s) => {
                 let iterable = iterables
                     .get(s.as_str())
-                    .ok_or(Error::UnknownIterable { name: s.clone() })?;
+                    .ok_or_else(|| Error::UnknownIterable { name: s.clone() })?;
                 Ok((s, iterable.clone()))
             }
             tera::Value::Object(o) => {
@@ -44,30 +45,32 @@ pub fn from_values(
         .collect::<Result<Matrix, Error>>()
 }
 
-fn magic_iterables(only_flavor: Option<FlavorName>) -> HashMap<&'static str, Vec<String>> {
-    HashMap::from([
-        (
-            "flavor",
-            only_flavor.map_or_else(
-                || {
-                    catppuccin::PALETTE
-                        .into_iter()
-                        .map(|flavor| flavor.identifier().to_string())
-                        .collect::<Vec<String>>()
-                },
-                |flavor| vec![flavor.identifier().to_string()],
-            ),
-        ),
-        ("accent", ctp_accents()),
-    ])
+fn magic_iterables(
+    palette: &Palette,
+    only_flavor: Option<&str>,
+) -> HashMap<&'static str, Vec<String>> {
+    let flavors = only_flavor.map_or_else(
+        || palette.flavors.keys().cloned().collect::<Vec<String>>(),
+        |flavor| vec![flavor.to_string()],
+    );
+    let accents = accents(palette, &flavors);
+    HashMap::from([("flavor", flavors), ("accent", accents)])
 }
 
-fn ctp_accents() -> Vec<String> {
-    catppuccin::PALETTE
-        .latte
-        .colors
+/// The identifiers of colors that are accents in every one of `flavors`.
+fn accents(palette: &Palette, flavors: &[String]) -> Vec<String> {
+    let mut flavors = flavors.iter().filter_map(|f| palette.flavors.get(f));
+    let Some(first) = flavors.next() else {
+        return vec![];
+    };
+    let rest = flavors.collect::<Vec<_>>();
+    first
         .iter()
-        .filter(|c| c.accent)
-        .map(|c| c.name.identifier().to_string())
+        .filter(|(_, color)| color.accent)
+        .filter(|(identifier, _)| {
+            rest.iter()
+                .all(|f| f.colors.get(*identifier).is_some_and(|c| c.accent))
+        })
+        .map(|(identifier, _)| identifier.clone())
         .collect()
 }
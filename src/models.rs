@@ -1,10 +1,16 @@
-use css_colors::Color as _;
+use std::path::Path;
+
 use indexmap::IndexMap;
 
-use crate::cli::ColorOverrides;
+use crate::{
+    cli::{ColorOverride, ColorOverrides},
+    colorspace::{self, BlendMode, Cvd, DeltaE, HueInterpolation, MixSpace},
+    parse,
+    terminal::{self, Ansi16Subset, Xterm256Subset},
+};
 
-// a frankenstein mix of Catppuccin & css_colors types to get all the
-// functionality we want.
+// a frankenstein mix of Catppuccin types & our own color spaces to get all
+// the functionality we want.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Palette {
     pub flavors: IndexMap<String, Flavor>,
@@ -19,6 +25,13 @@ pub struct Flavor {
     pub colors: IndexMap<String, Color>,
 }
 
+/// A palette color, or one derived from it by a filter or function.
+///
+/// Alpha is always straight (not premultiplied): `float.a` is the canonical
+/// opacity from 0 to 1, and `opacity` and any alpha in `hex` are that value
+/// quantized to 0..255. Hue, saturation, chroma and lightness adjustments
+/// leave alpha untouched, opacity adjustments change only alpha, and mixing
+/// interpolates premultiplied channels as CSS `color-mix()` does.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Color {
     pub name: String,
@@ -27,7 +40,14 @@ pub struct Color {
     pub hex: String,
     pub rgb: RGB,
     pub hsl: HSL,
+    pub oklab: OKLab,
+    pub oklch: OKLCH,
     pub opacity: u8,
+    /// The canonical, unquantized form of this color.
+    ///
+    /// Every other representation is derived from this, so that chains of
+    /// adjustments don't accumulate rounding errors.
+    pub float: FloatRGBA,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -39,146 +59,394 @@ pub struct RGB {
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct HSL {
-    pub h: u16,
-    pub s: f32,
-    pub l: f32,
+    pub h: f64,
+    pub s: f64,
+    pub l: f64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct OKLab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct OKLCH {
+    pub l: f64,
+    pub c: f64,
+    pub h: f64,
+}
+
+/// sRGB channels and alpha, each from 0 to 1.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FloatRGBA {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("Failed to parse hex color: {0}")]
-    ParseHex(#[from] std::num::ParseIntError),
+    #[error("Invalid hex color {hex:?} for `{identifier}` in `{flavor}` color overrides")]
+    InvalidHex {
+        flavor: String,
+        identifier: String,
+        hex: String,
+        #[source]
+        source: parse::Error,
+    },
+
+    #[error("Unknown color `{identifier}` in `{flavor}` color overrides (custom colors must be given as an object with a `hex` key)")]
+    UnknownColor { flavor: String, identifier: String },
+
+    #[error("Unknown flavor `{flavor}` in color overrides")]
+    UnknownFlavor { flavor: String },
+
+    #[error("Failed to read palette file: {path}")]
+    ReadPalette {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Invalid palette file {path}: {message}")]
+    InvalidPalette { path: String, message: String },
+
+    #[error("Derived flavor `{flavor}` is already defined")]
+    DuplicateFlavor { flavor: String },
+
+    #[error("Derived flavor `{flavor}` is based on unknown flavor `{from}`")]
+    UnknownBaseFlavor { flavor: String, from: String },
 }
 
-/// attempt to canonicalize a hex string, optionally capitalizing it and adding a prefix.
-fn format_hex(hex: &str, capitalize_hex_strings: bool, hex_prefix: Option<&str>) -> String {
-    let hex = hex.trim_start_matches('#');
-    let hex = if capitalize_hex_strings {
-        hex.to_uppercase()
-    } else {
-        hex.to_string()
+/// A flavor defined as a variation of another, such as an OLED variant of
+/// mocha with a pure black base.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct DerivedFlavor {
+    /// The identifier of the flavor to start from.
+    pub from: String,
+    /// The display name, defaulting to the derived flavor's identifier.
+    pub name: Option<String>,
+    /// Whether the flavor is dark, defaulting to that of the `from` flavor.
+    pub dark: Option<bool>,
+    /// Colors to replace or add, in the same form as color overrides.
+    #[serde(default)]
+    pub colors: IndexMap<String, ColorOverride>,
+}
+
+fn color_from_catppuccin(color: &catppuccin::Color) -> Color {
+    let rgb = [color.rgb.r, color.rgb.g, color.rgb.b].map(|c| f64::from(c) / 255.0);
+    Color::new(
+        color.name.to_string(),
+        color.name.identifier().to_string(),
+        color.accent,
+        rgb,
+        1.0,
+    )
+}
+
+/// The Catppuccin palette, from [`catppuccin::PALETTE`].
+#[must_use]
+pub fn default_palette() -> Palette {
+    let flavors = catppuccin::PALETTE
+        .into_iter()
+        .map(|flavor| {
+            let colors = flavor
+                .into_iter()
+                .map(|color| {
+                    (
+                        color.name.identifier().to_string(),
+                        color_from_catppuccin(color),
+                    )
+                })
+                .collect();
+            (
+                flavor.identifier().to_string(),
+                Flavor {
+                    name: flavor.name.to_string(),
+                    identifier: flavor.identifier().to_string(),
+                    dark: flavor.dark,
+                    light: !flavor.dark,
+                    colors,
+                },
+            )
+        })
+        .collect();
+    Palette { flavors }
+}
+
+/// Load a custom palette from a JSON or YAML file.
+///
+/// The file has the same shape as a serialized [`Palette`], but each color
+/// only needs a `name`, `hex` and optionally `accent`, and each flavor only
+/// needs a `name`, `dark` and `colors`. Identifiers are taken from the keys.
+pub fn load_palette(path: &Path) -> Result<Palette, Error> {
+    #[derive(serde::Deserialize)]
+    struct PaletteFile {
+        flavors: IndexMap<String, FlavorFile>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct FlavorFile {
+        name: String,
+        dark: bool,
+        colors: IndexMap<String, ColorFile>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct ColorFile {
+        name: String,
+        hex: String,
+        #[serde(default)]
+        accent: bool,
+    }
+
+    let invalid = |message: String| Error::InvalidPalette {
+        path: path.display().to_string(),
+        message,
     };
-    if let Some(prefix) = hex_prefix {
-        format!("{prefix}{hex}")
+    let contents = std::fs::read_to_string(path).map_err(|source| Error::ReadPalette {
+        path: path.display().to_string(),
+        source,
+    })?;
+    let file: PaletteFile = if path.extension().is_some_and(|e| e == "json") {
+        serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?
     } else {
-        hex
+        serde_yaml::from_str(&contents).map_err(|e| invalid(e.to_string()))?
+    };
+    if file.flavors.is_empty() {
+        return Err(invalid("palette has no flavors".to_string()));
+    }
+
+    let mut flavors = IndexMap::new();
+    for (flavor_id, flavor) in file.flavors {
+        let mut colors = IndexMap::new();
+        for (color_id, color) in flavor.colors {
+            let (rgb, alpha) = parse::hex(&color.hex)
+                .map_err(|e| invalid(format!("color `{color_id}` in flavor `{flavor_id}`: {e}")))?;
+            let color = Color::new(color.name, color_id.clone(), color.accent, rgb, alpha);
+            colors.insert(color_id, color);
+        }
+        flavors.insert(
+            flavor_id.clone(),
+            Flavor {
+                name: flavor.name,
+                identifier: flavor_id,
+                dark: flavor.dark,
+                light: !flavor.dark,
+                colors,
+            },
+        );
     }
+    Ok(Palette { flavors })
 }
 
-fn color_from_hex(
-    hex: &str,
-    blueprint: &catppuccin::Color,
-    capitalize_hex_strings: bool,
-    hex_prefix: Option<&str>,
-) -> Result<Color, Error> {
-    let i = u32::from_str_radix(hex, 16)?;
-    let rgb = RGB {
-        r: ((i >> 16) & 0xff) as u8,
-        g: ((i >> 8) & 0xff) as u8,
-        b: (i & 0xff) as u8,
+/// Check that every override names a flavor in `base`, and either a color in
+/// each flavor it targets or a custom color given in the object form.
+fn validate_overrides(base: &Palette, color_overrides: &ColorOverrides) -> Result<(), Error> {
+    // plain hex overrides must name a color in every flavor they target
+    let is_builtin = |flavor: &str, identifier: &str| {
+        if flavor == "all" {
+            base.flavors
+                .values()
+                .all(|flavor| flavor.colors.contains_key(identifier))
+        } else {
+            base.flavors
+                .get(flavor)
+                .is_some_and(|flavor| flavor.colors.contains_key(identifier))
+        }
+    };
+    // a custom color declared in any section can be overridden in the others
+    let is_custom = |identifier: &str| {
+        std::iter::once(&color_overrides.all)
+            .chain(color_overrides.flavors.values())
+            .any(|overrides| {
+                overrides
+                    .get(identifier)
+                    .is_some_and(ColorOverride::is_custom)
+            })
     };
-    let hsl = css_colors::rgb(rgb.r, rgb.g, rgb.b).to_hsl();
-    let hex = format_hex(hex, capitalize_hex_strings, hex_prefix);
-    Ok(Color {
-        name: blueprint.name.to_string(),
-        identifier: blueprint.name.identifier().to_string(),
-        accent: blueprint.accent,
-        hex,
-        rgb,
-        hsl: HSL {
-            h: hsl.h.degrees(),
-            s: hsl.s.as_f32(),
-            l: hsl.l.as_f32(),
-        },
-        opacity: 255,
-    })
-}
 
-fn color_from_catppuccin(
-    color: &catppuccin::Color,
-    capitalize_hex_strings: bool,
-    hex_prefix: Option<&str>,
-) -> Color {
-    let hex = format_hex(&color.hex.to_string(), capitalize_hex_strings, hex_prefix);
-    Color {
-        name: color.name.to_string(),
-        identifier: color.name.identifier().to_string(),
-        accent: color.accent,
-        hex,
-        rgb: RGB {
-            r: color.rgb.r,
-            g: color.rgb.g,
-            b: color.rgb.b,
-        },
-        hsl: HSL {
-            h: color.hsl.h.round() as u16,
-            s: color.hsl.s as f32,
-            l: color.hsl.l as f32,
-        },
-        opacity: 255,
+    let sections = std::iter::once(("all", &color_overrides.all)).chain(
+        color_overrides
+            .flavors
+            .iter()
+            .map(|(flavor, overrides)| (flavor.as_str(), overrides)),
+    );
+    for (flavor, overrides) in sections {
+        if flavor != "all" && !base.flavors.contains_key(flavor) {
+            return Err(Error::UnknownFlavor {
+                flavor: flavor.to_string(),
+            });
+        }
+        for (identifier, o) in overrides {
+            if !o.is_custom() && !is_builtin(flavor, identifier) && !is_custom(identifier) {
+                return Err(Error::UnknownColor {
+                    flavor: flavor.to_string(),
+                    identifier: identifier.clone(),
+                });
+            }
+        }
     }
+    Ok(())
 }
 
-/// Build a [`Palette`] from [`catppuccin::PALETTE`], optionally applying color overrides.
+/// Build a [`Palette`] from a base palette, optionally applying color overrides.
+///
+/// Overrides in the object form for identifiers that aren't in the palette
+/// add custom colors to the flavor, after the base colors.
 pub fn build_palette(
-    capitalize_hex_strings: bool,
-    hex_prefix: Option<&str>,
+    base: &Palette,
+    hex_format: &HexFormat,
     color_overrides: Option<&ColorOverrides>,
 ) -> Result<Palette, Error> {
-    // make a `Color` from a `catppuccin::Color`, taking into account `color_overrides`.
-    // overrides apply in this order:
-    // 1. base color
-    // 2. "all" override
-    // 3. flavor override
-    let make_color =
-        |color: &catppuccin::Color, flavor_name: catppuccin::FlavorName| -> Result<Color, Error> {
-            let flavor_override = color_overrides
-                .map(|co| match flavor_name {
-                    catppuccin::FlavorName::Latte => &co.latte,
-                    catppuccin::FlavorName::Frappe => &co.frappe,
-                    catppuccin::FlavorName::Macchiato => &co.macchiato,
-                    catppuccin::FlavorName::Mocha => &co.mocha,
+    if let Some(color_overrides) = color_overrides {
+        validate_overrides(base, color_overrides)?;
+    }
+    let no_overrides = IndexMap::new();
+    let all_overrides = color_overrides.map_or(&no_overrides, |co| &co.all);
+    // custom colors declared in the object form in any section, so that other
+    // sections can override them with a plain hex string
+    let declarations: IndexMap<&str, &ColorOverride> = color_overrides
+        .into_iter()
+        .flat_map(|co| std::iter::once(&co.all).chain(co.flavors.values()))
+        .flatten()
+        .filter(|(_, o)| o.is_custom())
+        .map(|(identifier, o)| (identifier.as_str(), o))
+        .collect();
+
+    let mut flavors = IndexMap::new();
+    for (flavor_id, flavor) in base {
+        let flavor_overrides = color_overrides
+            .and_then(|co| co.flavors.get(flavor_id))
+            .unwrap_or(&no_overrides);
+
+        // make a `Color` from a hex override, taking each field from the first of:
+        // 1. flavor override
+        // 2. "all" override
+        // 3. base color, or the custom color's declaration
+        let make_color = |identifier: &str, base: Option<&Color>| {
+            let overrides = [
+                (flavor_id.as_str(), flavor_overrides.get(identifier)),
+                ("all", all_overrides.get(identifier)),
+            ];
+            let overrides = overrides
+                .iter()
+                .filter_map(|(section, o)| o.map(|o| (*section, o)));
+            let Some((section, hex)) = overrides.clone().map(|(s, o)| (s, o.hex())).next() else {
+                return base.cloned().map(Ok);
+            };
+            let name = overrides
+                .clone()
+                .find_map(|(_, o)| o.name())
+                .map(ToString::to_string)
+                .or_else(|| base.map(|c| c.name.clone()))
+                .or_else(|| {
+                    declarations
+                        .get(identifier)
+                        .and_then(|o| o.name())
+                        .map(ToString::to_string)
                 })
-                .and_then(|o| o.get(color.name.identifier()).cloned())
-                .map(|s| color_from_hex(&s, color, capitalize_hex_strings, hex_prefix))
-                .transpose()?;
-
-            let all_override = color_overrides
-                .and_then(|co| co.all.get(color.name.identifier()).cloned())
-                .map(|s| color_from_hex(&s, color, capitalize_hex_strings, hex_prefix))
-                .transpose()?;
-
-            Ok(flavor_override.or(all_override).unwrap_or_else(|| {
-                color_from_catppuccin(color, capitalize_hex_strings, hex_prefix)
-            }))
+                .unwrap_or_else(|| identifier.to_string());
+            let accent = overrides
+                .clone()
+                .find_map(|(_, o)| o.accent())
+                .or_else(|| base.map(|c| c.accent))
+                .or_else(|| declarations.get(identifier).and_then(|o| o.accent()))
+                .unwrap_or(false);
+            Some(
+                parse::hex(hex)
+                    .map(|(rgb, alpha)| {
+                        Color::new(name, identifier.to_string(), accent, rgb, alpha)
+                    })
+                    .map_err(|source| Error::InvalidHex {
+                        flavor: section.to_string(),
+                        identifier: identifier.to_string(),
+                        hex: hex.to_string(),
+                        source,
+                    }),
+            )
         };
 
-    let mut flavors = IndexMap::new();
-    for flavor in &catppuccin::PALETTE {
         let mut colors = IndexMap::new();
-        for color in flavor {
-            colors.insert(
-                color.name.identifier().to_string(),
-                make_color(color, flavor.name)?,
-            );
+        for (identifier, color) in flavor {
+            if let Some(color) = make_color(identifier, Some(color)) {
+                colors.insert(identifier.clone(), color?.formatted(hex_format));
+            }
         }
+        for (identifier, o) in all_overrides.iter().chain(flavor_overrides) {
+            let is_custom = o.is_custom() || declarations.contains_key(identifier.as_str());
+            if !is_custom || colors.contains_key(identifier) {
+                continue;
+            }
+            if let Some(color) = make_color(identifier, None) {
+                colors.insert(identifier.clone(), color?.formatted(hex_format));
+            }
+        }
+
         flavors.insert(
-            flavor.name.identifier().to_string(),
+            flavor_id.clone(),
             Flavor {
-                name: flavor.name.to_string(),
-                identifier: flavor.name.identifier().to_string(),
-                dark: flavor.dark,
-                light: !flavor.dark,
                 colors,
+                ..flavor.clone()
             },
         );
     }
     Ok(Palette { flavors })
 }
 
+/// Add derived flavors to a base palette, after its own flavors.
+///
+/// Flavors are derived in order, so a derived flavor can be based on one
+/// defined before it.
+pub fn derive_flavors(
+    base: &Palette,
+    derived: &IndexMap<String, DerivedFlavor>,
+) -> Result<Palette, Error> {
+    let mut palette = base.clone();
+    for (flavor_id, derived) in derived {
+        if palette.flavors.contains_key(flavor_id) {
+            return Err(Error::DuplicateFlavor {
+                flavor: flavor_id.clone(),
+            });
+        }
+        let from = palette
+            .flavors
+            .get(&derived.from)
+            .ok_or_else(|| Error::UnknownBaseFlavor {
+                flavor: flavor_id.clone(),
+                from: derived.from.clone(),
+            })?;
+        let dark = derived.dark.unwrap_or(from.dark);
+        let flavor = Flavor {
+            name: derived.name.clone().unwrap_or_else(|| flavor_id.clone()),
+            identifier: flavor_id.clone(),
+            dark,
+            light: !dark,
+            ..from.clone()
+        };
+
+        // the derived colors are applied just like overrides for this flavor
+        let source = Palette {
+            flavors: IndexMap::from([(flavor_id.clone(), flavor)]),
+        };
+        let overrides = ColorOverrides {
+            all: IndexMap::new(),
+            flavors: IndexMap::from([(flavor_id.clone(), derived.colors.clone())]),
+        };
+        let mut built = build_palette(&source, &HexFormat::default(), Some(&overrides))?;
+        if let Some(flavor) = built.flavors.swap_remove(flavor_id) {
+            palette.flavors.insert(flavor_id.clone(), flavor);
+        }
+    }
+    Ok(palette)
+}
+
 impl Palette {
     #[must_use]
-    pub fn iter(&self) -> indexmap::map::Iter<String, Flavor> {
+    pub fn iter(&self) -> indexmap::map::Iter<'_, String, Flavor> {
         self.flavors.iter()
     }
 }
@@ -193,8 +461,17 @@ impl<'a> IntoIterator for &'a Palette {
 }
 
 impl Flavor {
+    /// The color in this flavor perceptually closest to `color`.
     #[must_use]
-    pub fn iter(&self) -> indexmap::map::Iter<String, Color> {
+    pub fn nearest(&self, color: &Color, method: DeltaE) -> Option<&Color> {
+        self.colors.values().min_by(|a, b| {
+            a.delta_e(color, method)
+                .total_cmp(&b.delta_e(color, method))
+        })
+    }
+
+    #[must_use]
+    pub fn iter(&self) -> indexmap::map::Iter<'_, String, Color> {
         self.colors.iter()
     }
 }
@@ -208,208 +485,572 @@ impl<'a> IntoIterator for &'a Flavor {
     }
 }
 
-fn rgb_to_hex(rgb: &RGB, opacity: u8) -> String {
-    if opacity < 255 {
-        format!("{:02x}{:02x}{:02x}{:02x}", rgb.r, rgb.g, rgb.b, opacity)
-    } else {
-        format!("{:02x}{:02x}{:02x}", rgb.r, rgb.g, rgb.b)
+/// Quantize a channel from 0 to 1 into a byte.
+fn quantize(c: f64) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Where to put the alpha channel in hex strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HexAlpha {
+    /// `RRGGBBAA`, with alpha only included if the color is translucent.
+    #[default]
+    Suffix,
+    /// `AARRGGBB`, with alpha only included if the color is translucent.
+    Prefix,
+    /// `RRGGBBAA`, even if the color is opaque.
+    Always,
+    /// `AARRGGBB`, even if the color is opaque.
+    AlwaysPrefix,
+    /// `RRGGBB`, never including alpha.
+    Never,
+}
+
+/// How colors are written as hex strings.
+#[derive(Clone, Debug, Default)]
+pub struct HexFormat {
+    pub prefix: Option<String>,
+    pub capitalize: bool,
+    pub alpha: HexAlpha,
+}
+
+impl HexFormat {
+    #[must_use]
+    pub fn format(&self, rgb: &RGB, opacity: u8) -> String {
+        let RGB { r, g, b } = rgb;
+        let translucent = opacity < 255;
+        let hex = match self.alpha {
+            HexAlpha::Suffix if translucent => format!("{r:02x}{g:02x}{b:02x}{opacity:02x}"),
+            HexAlpha::Always => format!("{r:02x}{g:02x}{b:02x}{opacity:02x}"),
+            HexAlpha::Prefix if translucent => format!("{opacity:02x}{r:02x}{g:02x}{b:02x}"),
+            HexAlpha::AlwaysPrefix => format!("{opacity:02x}{r:02x}{g:02x}{b:02x}"),
+            HexAlpha::Suffix | HexAlpha::Prefix | HexAlpha::Never => {
+                format!("{r:02x}{g:02x}{b:02x}")
+            }
+        };
+        let hex = if self.capitalize {
+            hex.to_uppercase()
+        } else {
+            hex
+        };
+        format!("{}{hex}", self.prefix.as_deref().unwrap_or_default())
     }
 }
 
 impl Color {
-    fn from_hsla(hsla: css_colors::HSLA, blueprint: &Self) -> Self {
-        let rgb = hsla.to_rgb();
-        let rgb = RGB {
-            r: rgb.r.as_u8(),
-            g: rgb.g.as_u8(),
-            b: rgb.b.as_u8(),
+    /// Build a color from unquantized sRGB channels and alpha, each from 0 to 1.
+    #[allow(clippy::many_single_char_names)]
+    #[must_use]
+    pub fn new(name: String, identifier: String, accent: bool, rgb: [f64; 3], alpha: f64) -> Self {
+        let [r, g, b] = rgb.map(|c| c.clamp(0.0, 1.0));
+        let a = alpha.clamp(0.0, 1.0);
+
+        let quantized = RGB {
+            r: quantize(r),
+            g: quantize(g),
+            b: quantize(b),
         };
-        let hsl = HSL {
-            h: hsla.h.degrees(),
-            s: hsla.s.as_f32(),
-            l: hsla.l.as_f32(),
+        let opacity = quantize(a);
+        let [h, s, l] = colorspace::srgb_to_hsl([r, g, b]);
+        let [ok_l, ok_a, ok_b] = colorspace::srgb_to_oklab([r, g, b]);
+        let oklab = OKLab {
+            l: ok_l,
+            a: ok_a,
+            b: ok_b,
         };
-        let opacity = hsla.a.as_u8();
+
         Self {
-            name: blueprint.name.clone(),
-            identifier: blueprint.identifier.clone(),
-            accent: blueprint.accent,
-            hex: rgb_to_hex(&rgb, opacity),
-            rgb,
-            hsl,
+            name,
+            identifier,
+            accent,
+            hex: HexFormat::default().format(&quantized, opacity),
+            rgb: quantized,
+            hsl: HSL { h, s, l },
+            oklch: OKLCH::from(&oklab),
+            oklab,
             opacity,
+            float: FloatRGBA { r, g, b, a },
         }
     }
 
-    fn from_rgba(rgba: css_colors::RGBA, blueprint: &Self) -> Self {
-        let hsl = rgba.to_hsl();
-        let rgb = RGB {
-            r: rgba.r.as_u8(),
-            g: rgba.g.as_u8(),
-            b: rgba.b.as_u8(),
-        };
-        let hsl = HSL {
-            h: hsl.h.degrees(),
-            s: hsl.s.as_f32(),
-            l: hsl.l.as_f32(),
-        };
-        let opacity = rgba.a.as_u8();
+    /// This color as a hex string in the given format.
+    #[must_use]
+    pub fn hex_with(&self, format: &HexFormat) -> String {
+        format.format(&self.rgb, self.opacity)
+    }
+
+    /// This color with its `hex` field rewritten in the given format.
+    #[must_use]
+    pub fn formatted(self, format: &HexFormat) -> Self {
         Self {
-            name: blueprint.name.clone(),
-            identifier: blueprint.identifier.clone(),
-            accent: blueprint.accent,
-            hex: rgb_to_hex(&rgb, opacity),
-            rgb,
-            hsl,
-            opacity,
+            hex: self.hex_with(format),
+            ..self
         }
     }
 
+    /// Build a new color with the same name, identifier, and accent flag as this one.
+    fn derive(&self, rgb: [f64; 3], alpha: f64) -> Self {
+        Self::new(
+            self.name.clone(),
+            self.identifier.clone(),
+            self.accent,
+            rgb,
+            alpha,
+        )
+    }
+
+    fn with_hsl(&self, [h, s, l]: [f64; 3]) -> Self {
+        let hsl = [h.rem_euclid(360.0), s.clamp(0.0, 1.0), l.clamp(0.0, 1.0)];
+        self.derive(colorspace::hsl_to_srgb(hsl), self.float.a)
+    }
+
+    fn with_oklch(&self, oklch: &OKLCH) -> Self {
+        let srgb = colorspace::oklch_to_srgb_gamut_mapped([oklch.l, oklch.c, oklch.h]);
+        self.derive(srgb, self.float.a)
+    }
+
+    const fn srgb(&self) -> [f64; 3] {
+        [self.float.r, self.float.g, self.float.b]
+    }
+
+    const fn hsl(&self) -> [f64; 3] {
+        [self.hsl.h, self.hsl.s, self.hsl.l]
+    }
+
+    /// Mix two colors in sRGB space. `amount` is the proportion of `base` in the result.
     #[must_use]
     pub fn mix(base: &Self, blend: &Self, amount: f64) -> Self {
-        let amount = (amount * 100.0).round() as u8;
-        let blueprint = base;
-        let base: css_colors::RGBA = base.into();
-        let base = base.to_rgba();
-        let blend: css_colors::RGBA = blend.into();
-        let result = base.mix(blend, css_colors::percent(amount));
-        Self::from_rgba(result, blueprint)
+        Self::mix_in(
+            base,
+            blend,
+            amount,
+            MixSpace::Srgb,
+            HueInterpolation::default(),
+        )
     }
 
+    /// Mix two colors in the given color space.
+    ///
+    /// As with [`Color::mix`], `amount` is the proportion of `base` in the result.
+    /// `hue` only applies when mixing in [`MixSpace::Oklch`].
     #[must_use]
-    pub fn mod_hue(&self, hue: i32) -> Self {
-        let mut hsl: css_colors::HSL = self.into();
-        hsl.h = css_colors::deg(hue);
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn mix_in(
+        base: &Self,
+        blend: &Self,
+        amount: f64,
+        space: MixSpace,
+        hue: HueInterpolation,
+    ) -> Self {
+        let t = 1.0 - amount.clamp(0.0, 1.0);
+        let (srgb, alpha) = colorspace::mix_srgba(
+            (base.srgb(), base.float.a),
+            (blend.srgb(), blend.float.a),
+            t,
+            space,
+            hue,
+        );
+        base.derive(srgb, alpha)
     }
 
+    /// Blend `top` onto this color with a W3C blend mode, then composite the
+    /// result over this color with `top`'s alpha scaled by `opacity`.
     #[must_use]
-    pub fn add_hue(&self, hue: i32) -> Self {
-        let hsl: css_colors::HSL = self.into();
-        let hsl = hsl.spin(css_colors::deg(hue));
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn blend(&self, top: &Self, mode: BlendMode, opacity: f64) -> Self {
+        let (backdrop, source) = (self.srgb(), top.srgb());
+        let (ab, as_) = (self.float.a, top.float.a * opacity.clamp(0.0, 1.0));
+        let blended = colorspace::blend(backdrop, source, mode);
+        // the blend only applies where the backdrop is present, per the spec
+        let source = [0, 1, 2].map(|i| colorspace::lerp(source[i], blended[i], ab));
+        let alpha = ab.mul_add(1.0 - as_, as_);
+        if alpha <= 0.0 {
+            return self.derive(backdrop, 0.0);
+        }
+        let rgb = [0, 1, 2].map(|i| source[i].mul_add(as_, backdrop[i] * ab * (1.0 - as_)) / alpha);
+        self.derive(rgb, alpha)
     }
 
+    /// The WCAG 2.x relative luminance of this color, ignoring opacity.
     #[must_use]
-    pub fn sub_hue(&self, hue: i32) -> Self {
-        let hsl: css_colors::HSL = self.into();
-        let hsl = hsl.spin(-css_colors::deg(hue));
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn luminance(&self) -> f64 {
+        colorspace::relative_luminance(self.srgb())
     }
 
+    /// The WCAG 2.x contrast ratio between this color and another.
     #[must_use]
-    pub fn mod_saturation(&self, saturation: u8) -> Self {
-        let mut hsl: css_colors::HSL = self.into();
-        hsl.s = css_colors::percent(saturation);
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn contrast(&self, other: &Self) -> f64 {
+        colorspace::contrast_ratio(self.luminance(), other.luminance())
     }
 
+    /// The perceptual difference between this color and another, ignoring opacity.
     #[must_use]
-    pub fn add_saturation(&self, saturation: u8) -> Self {
-        let hsl: css_colors::HSL = self.into();
-        let hsl = hsl.saturate(css_colors::percent(saturation));
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn delta_e(&self, other: &Self, method: DeltaE) -> f64 {
+        colorspace::delta_e(self.srgb(), other.srgb(), method)
     }
 
+    /// Shift this color's Oklch lightness as little as possible so that it
+    /// has at least `ratio` contrast against `against`.
+    ///
+    /// Moving away from the background is tried first, then towards (and past)
+    /// it. Returns `None` if neither direction can reach the target.
     #[must_use]
-    pub fn sub_saturation(&self, saturation: u8) -> Self {
-        let hsl: css_colors::HSL = self.into();
-        let hsl = hsl.desaturate(css_colors::percent(saturation));
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn ensure_contrast(&self, against: &Self, ratio: f64) -> Option<Self> {
+        if self.contrast(against) >= ratio {
+            return Some(self.clone());
+        }
+
+        let current = self.oklch.l;
+        let extremes = if self.luminance() <= against.luminance() {
+            [0.0, 1.0]
+        } else {
+            [1.0, 0.0]
+        };
+        extremes.into_iter().find_map(|extreme| {
+            let meets = |lightness| self.mod_oklch_lightness(lightness).contrast(against) >= ratio;
+            if !meets(extreme) {
+                return None;
+            }
+            let (mut fail, mut pass) = (current, extreme);
+            for _ in 0..32 {
+                let mid = f64::midpoint(fail, pass);
+                if meets(mid) {
+                    pass = mid;
+                } else {
+                    fail = mid;
+                }
+            }
+            Some(self.mod_oklch_lightness(pass))
+        })
     }
 
+    /// Simulate how this color appears with a color vision deficiency.
     #[must_use]
-    pub fn mod_lightness(&self, lightness: u8) -> Self {
-        let mut hsl: css_colors::HSL = self.into();
-        hsl.l = css_colors::percent(lightness);
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn simulate(&self, cvd: Cvd, severity: f64) -> Self {
+        let srgb = colorspace::simulate_cvd(self.srgb(), cvd, severity);
+        self.derive(srgb, self.float.a)
     }
 
+    /// Composite this color over a background with the "source over" operator.
+    ///
+    /// The result is opaque as long as the background is.
     #[must_use]
-    pub fn add_lightness(&self, lightness: u8) -> Self {
-        let hsl: css_colors::HSL = self.into();
-        let hsl = hsl.lighten(css_colors::percent(lightness));
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn flatten(&self, over: &Self) -> Self {
+        let (fg, bg) = (self.float.a, over.float.a);
+        let alpha = bg.mul_add(1.0 - fg, fg);
+        if alpha <= 0.0 {
+            return self.derive(over.srgb(), 0.0);
+        }
+        let rgb =
+            [0, 1, 2].map(|i| self.srgb()[i].mul_add(fg, over.srgb()[i] * bg * (1.0 - fg)) / alpha);
+        self.derive(rgb, alpha)
     }
 
+    /// Invert each sRGB channel, keeping opacity.
     #[must_use]
-    pub fn sub_lightness(&self, lightness: u8) -> Self {
-        let hsl: css_colors::HSL = self.into();
-        let hsl = hsl.darken(css_colors::percent(lightness));
-        Self::from_hsla(hsl.to_hsla(), self)
+    pub fn invert(&self) -> Self {
+        self.derive(self.srgb().map(|c| 1.0 - c), self.float.a)
     }
 
+    /// Convert to the gray with the same relative luminance, keeping opacity.
     #[must_use]
-    pub fn mod_opacity(&self, opacity: f32) -> Self {
-        let opacity = (opacity * 255.0).round() as u8;
-        Self {
-            opacity,
-            hex: rgb_to_hex(&self.rgb, opacity),
-            ..self.clone()
+    pub fn grayscale(&self) -> Self {
+        let gray = colorspace::linear_to_srgb(self.luminance());
+        self.derive([gray; 3], self.float.a)
+    }
+
+    #[must_use]
+    pub fn mod_hue(&self, hue: f64) -> Self {
+        let [_, s, l] = self.hsl();
+        self.with_hsl([hue, s, l])
+    }
+
+    #[must_use]
+    pub fn add_hue(&self, hue: f64) -> Self {
+        let [h, s, l] = self.hsl();
+        self.with_hsl([h + hue, s, l])
+    }
+
+    #[must_use]
+    pub fn sub_hue(&self, hue: f64) -> Self {
+        let [h, s, l] = self.hsl();
+        self.with_hsl([h - hue, s, l])
+    }
+
+    #[must_use]
+    pub fn mod_saturation(&self, saturation: f64) -> Self {
+        let [h, _, l] = self.hsl();
+        self.with_hsl([h, saturation, l])
+    }
+
+    #[must_use]
+    pub fn add_saturation(&self, saturation: f64) -> Self {
+        let [h, s, l] = self.hsl();
+        self.with_hsl([h, s + saturation, l])
+    }
+
+    #[must_use]
+    pub fn sub_saturation(&self, saturation: f64) -> Self {
+        let [h, s, l] = self.hsl();
+        self.with_hsl([h, s - saturation, l])
+    }
+
+    #[must_use]
+    pub fn mod_lightness(&self, lightness: f64) -> Self {
+        let [h, s, _] = self.hsl();
+        self.with_hsl([h, s, lightness])
+    }
+
+    #[must_use]
+    pub fn add_lightness(&self, lightness: f64) -> Self {
+        let [h, s, l] = self.hsl();
+        self.with_hsl([h, s, l + lightness])
+    }
+
+    #[must_use]
+    pub fn sub_lightness(&self, lightness: f64) -> Self {
+        let [h, s, l] = self.hsl();
+        self.with_hsl([h, s, l - lightness])
+    }
+
+    #[must_use]
+    pub fn mod_oklch_hue(&self, hue: f64) -> Self {
+        let oklch = OKLCH {
+            h: hue.rem_euclid(360.0),
+            ..self.oklch.clone()
+        };
+        self.with_oklch(&oklch)
+    }
+
+    #[must_use]
+    pub fn add_oklch_hue(&self, hue: f64) -> Self {
+        self.mod_oklch_hue(self.oklch.h + hue)
+    }
+
+    #[must_use]
+    pub fn sub_oklch_hue(&self, hue: f64) -> Self {
+        self.mod_oklch_hue(self.oklch.h - hue)
+    }
+
+    #[must_use]
+    pub fn mod_oklch_chroma(&self, chroma: f64) -> Self {
+        let oklch = OKLCH {
+            c: chroma.max(0.0),
+            ..self.oklch.clone()
+        };
+        self.with_oklch(&oklch)
+    }
+
+    #[must_use]
+    pub fn add_oklch_chroma(&self, chroma: f64) -> Self {
+        self.mod_oklch_chroma(self.oklch.c + chroma)
+    }
+
+    #[must_use]
+    pub fn sub_oklch_chroma(&self, chroma: f64) -> Self {
+        self.mod_oklch_chroma(self.oklch.c - chroma)
+    }
+
+    #[must_use]
+    pub fn mod_oklch_lightness(&self, lightness: f64) -> Self {
+        let oklch = OKLCH {
+            l: lightness.clamp(0.0, 1.0),
+            ..self.oklch.clone()
+        };
+        self.with_oklch(&oklch)
+    }
+
+    #[must_use]
+    pub fn add_oklch_lightness(&self, lightness: f64) -> Self {
+        self.mod_oklch_lightness(self.oklch.l + lightness)
+    }
+
+    #[must_use]
+    pub fn sub_oklch_lightness(&self, lightness: f64) -> Self {
+        self.mod_oklch_lightness(self.oklch.l - lightness)
+    }
+
+    #[must_use]
+    pub fn mod_opacity(&self, opacity: f64) -> Self {
+        self.derive(self.srgb(), opacity)
+    }
+
+    #[must_use]
+    pub fn add_opacity(&self, opacity: f64) -> Self {
+        self.derive(self.srgb(), self.float.a + opacity)
+    }
+
+    #[must_use]
+    pub fn sub_opacity(&self, opacity: f64) -> Self {
+        self.derive(self.srgb(), self.float.a - opacity)
+    }
+}
+
+/// Format a number with at most `precision` decimal places, dropping trailing zeroes.
+fn format_decimal(value: f64, precision: usize) -> String {
+    let s = format!("{value:.precision$}");
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Formatting of colors into the notations used by common targets.
+impl Color {
+    const fn is_opaque(&self) -> bool {
+        self.opacity == 255
+    }
+
+    /// `rgb(r, g, b)`, or `rgba(r, g, b, a)` if the color is translucent.
+    #[must_use]
+    pub fn css_rgb(&self) -> String {
+        let RGB { r, g, b } = self.rgb;
+        if self.is_opaque() {
+            format!("rgb({r}, {g}, {b})")
+        } else {
+            format!("rgba({r}, {g}, {b}, {})", format_decimal(self.float.a, 3))
         }
     }
 
+    /// `hsl(h, s%, l%)`, or `hsla(h, s%, l%, a)` if the color is translucent.
     #[must_use]
-    pub fn add_opacity(&self, opacity: f32) -> Self {
-        let opacity = (opacity * 255.0).round() as u8;
-        let opacity = self.opacity.saturating_add(opacity);
-        Self {
-            opacity,
-            hex: rgb_to_hex(&self.rgb, opacity),
-            ..self.clone()
+    pub fn css_hsl(&self) -> String {
+        let h = format_decimal(self.hsl.h, 0);
+        let s = format_decimal(self.hsl.s * 100.0, 0);
+        let l = format_decimal(self.hsl.l * 100.0, 0);
+        if self.is_opaque() {
+            format!("hsl({h}, {s}%, {l}%)")
+        } else {
+            let a = format_decimal(self.float.a, 3);
+            format!("hsla({h}, {s}%, {l}%, {a})")
         }
     }
 
+    /// `oklch(l% c h)`, or `oklch(l% c h / a)` if the color is translucent.
     #[must_use]
-    pub fn sub_opacity(&self, opacity: f32) -> Self {
-        let opacity = (opacity * 255.0).round() as u8;
-        let opacity = self.opacity.saturating_sub(opacity);
-        Self {
-            opacity,
-            hex: rgb_to_hex(&self.rgb, opacity),
-            ..self.clone()
+    pub fn css_oklch(&self) -> String {
+        let l = format_decimal(self.oklch.l * 100.0, 2);
+        let c = format_decimal(self.oklch.c, 4);
+        let h = format_decimal(self.oklch.h, 2);
+        if self.is_opaque() {
+            format!("oklch({l}% {c} {h})")
+        } else {
+            let a = format_decimal(self.float.a, 3);
+            format!("oklch({l}% {c} {h} / {a})")
         }
     }
-}
 
-impl From<&Color> for css_colors::RGB {
-    fn from(c: &Color) -> Self {
-        Self {
-            r: css_colors::Ratio::from_u8(c.rgb.r),
-            g: css_colors::Ratio::from_u8(c.rgb.g),
-            b: css_colors::Ratio::from_u8(c.rgb.b),
+    /// The color as a `0xRRGGBB` integer, or `0xRRGGBBAA` if `alpha` is true.
+    ///
+    /// The layout depends only on `alpha`, never on the color's opacity.
+    #[must_use]
+    pub fn rgb_int(&self, alpha: bool) -> u32 {
+        let rgb = u32::from(self.rgb.r) << 16 | u32::from(self.rgb.g) << 8 | u32::from(self.rgb.b);
+        if alpha {
+            rgb << 8 | u32::from(self.opacity)
+        } else {
+            rgb
         }
     }
-}
 
-impl From<&Color> for css_colors::RGBA {
-    fn from(c: &Color) -> Self {
-        Self {
-            r: css_colors::Ratio::from_u8(c.rgb.r),
-            g: css_colors::Ratio::from_u8(c.rgb.g),
-            b: css_colors::Ratio::from_u8(c.rgb.b),
-            a: css_colors::percent(c.opacity),
+    /// The color as a `0x00BBGGRR` integer, as used by Windows' `COLORREF`.
+    ///
+    /// This format has no alpha channel, so opacity is ignored.
+    #[must_use]
+    pub fn bgr_int(&self) -> u32 {
+        u32::from(self.rgb.b) << 16 | u32::from(self.rgb.g) << 8 | u32::from(self.rgb.r)
+    }
+
+    /// The color's channels as floats from 0 to 1, rounded to `precision` decimal places.
+    ///
+    /// Alpha is included if the color is translucent, or if `alpha` is true.
+    #[must_use]
+    pub fn floats(&self, precision: i32, alpha: bool) -> Vec<f64> {
+        let scale = 10f64.powi(precision);
+        let round = |c: f64| (c * scale).round() / scale;
+        let mut floats = vec![
+            round(self.float.r),
+            round(self.float.g),
+            round(self.float.b),
+        ];
+        if alpha || !self.is_opaque() {
+            floats.push(round(self.float.a));
         }
+        floats
+    }
+
+    /// The closest xterm 256-color palette index. Opacity is ignored.
+    #[must_use]
+    pub fn xterm256(&self, subset: Xterm256Subset) -> u8 {
+        terminal::nearest_xterm256(self.srgb(), subset)
+    }
+
+    /// The closest ANSI 16-color index. Opacity is ignored.
+    #[must_use]
+    pub fn ansi16(&self, subset: Ansi16Subset) -> u8 {
+        terminal::nearest_ansi16(self.srgb(), subset)
     }
 }
 
-impl From<&Color> for css_colors::HSL {
-    fn from(c: &Color) -> Self {
-        Self {
-            h: css_colors::Angle::new(c.hsl.h),
-            s: css_colors::Ratio::from_f32(c.hsl.s),
-            l: css_colors::Ratio::from_f32(c.hsl.l),
-        }
+impl From<&OKLab> for OKLCH {
+    fn from(lab: &OKLab) -> Self {
+        let [l, c, h] = colorspace::oklab_to_oklch([lab.l, lab.a, lab.b]);
+        Self { l, c, h }
     }
 }
 
-impl From<&Color> for css_colors::HSLA {
-    fn from(c: &Color) -> Self {
-        Self {
-            h: css_colors::Angle::new(c.hsl.h),
-            s: css_colors::Ratio::from_f32(c.hsl.s),
-            l: css_colors::Ratio::from_f32(c.hsl.l),
-            a: css_colors::Ratio::from_u8(c.opacity),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translucent_red() -> Color {
+        Color::new(
+            "Red".to_string(),
+            "red".to_string(),
+            true,
+            [1.0, 0.0, 0.0],
+            0.4,
+        )
+    }
+
+    #[test]
+    fn conversions_agree_on_alpha() {
+        let color = translucent_red();
+        assert_eq!(color.opacity, 102);
+        assert_eq!(color.hex, "ff000066");
+
+        let value = serde_json::to_value(&color).expect("color serializes");
+        assert_eq!(value["opacity"], 102);
+        assert_eq!(value["hex"], "ff000066");
+        assert_eq!(value["float"]["a"], 0.4);
+    }
+
+    #[test]
+    fn adjustments_keep_alpha() {
+        let color = translucent_red();
+        for adjusted in [
+            color.add_hue(30.0),
+            color.sub_saturation(0.2),
+            color.mod_lightness(0.3),
+            color.add_oklch_chroma(0.01),
+            color.invert(),
+            color.grayscale(),
+        ] {
+            assert!((adjusted.float.a - 0.4).abs() < f64::EPSILON);
         }
+        assert!((color.add_opacity(0.1).float.a - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn alpha_survives_serialization() {
+        let color = translucent_red().mod_opacity(0.123_456);
+        let value = serde_json::to_value(&color).expect("color serializes");
+        let color: Color = serde_json::from_value(value).expect("color deserializes");
+        assert!((color.float.a - 0.123_456).abs() < f64::EPSILON);
     }
 }
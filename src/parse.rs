@@ -0,0 +1,181 @@
+//! Parsing of color literals written in templates and configuration.
+//!
+//! Parsed colors are returned as unquantized sRGB channels and alpha, each
+//! from 0 to 1, ready for [`crate::models::Color::new`].
+
+use crate::colorspace::{self, Rgba};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid hex color {0:?}: expected 3, 4, 6 or 8 hex digits")]
+    Hex(String),
+    #[error("unrecognized color {0:?}: expected hex, rgb(), hsl() or oklch()")]
+    Syntax(String),
+    #[error("invalid value {value:?} in color {input:?}")]
+    Component { input: String, value: String },
+}
+
+/// Parse a hex color of 3, 4, 6 or 8 digits, with or without a leading `#`.
+pub fn hex(input: &str) -> Result<Rgba, Error> {
+    let digits = input.strip_prefix('#').unwrap_or(input);
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::Hex(input.to_string()));
+    }
+    let nibble = |i: usize| u8::from_str_radix(&digits[i..=i], 16).map(|n| n * 0x11);
+    let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16);
+    let channels: Result<Vec<u8>, _> = match digits.len() {
+        3 => (0..3).map(nibble).chain([Ok(0xff)]).collect(),
+        4 => (0..4).map(nibble).collect(),
+        6 => (0..3).map(|i| byte(i * 2)).chain([Ok(0xff)]).collect(),
+        8 => (0..4).map(|i| byte(i * 2)).collect(),
+        _ => return Err(Error::Hex(input.to_string())),
+    };
+    let channels = channels.map_err(|_| Error::Hex(input.to_string()))?;
+    let [r, g, b, a] = [0, 1, 2, 3].map(|i| f64::from(channels[i]) / 255.0);
+    Ok(([r, g, b], a))
+}
+
+/// A single numeric component of a CSS color function.
+#[derive(Clone, Copy)]
+enum Component {
+    Number(f64),
+    Percentage(f64),
+}
+
+impl Component {
+    /// Parse a number or percentage. A `deg` unit is only accepted if the
+    /// component is a hue `angle`.
+    fn parse(input: &str, value: &str, angle: bool) -> Result<Self, Error> {
+        let invalid = || Error::Component {
+            input: input.to_string(),
+            value: value.to_string(),
+        };
+        let number = |s: &str| {
+            s.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(invalid)
+        };
+        if let Some(percentage) = value.strip_suffix('%') {
+            Ok(Self::Percentage(number(percentage)? / 100.0))
+        } else {
+            let value = if angle {
+                value.strip_suffix("deg").unwrap_or(value)
+            } else {
+                value
+            };
+            Ok(Self::Number(number(value)?))
+        }
+    }
+
+    /// Resolve the component, scaling plain numbers by `number_scale` and
+    /// percentages by `percentage_scale`.
+    fn resolve(self, number_scale: f64, percentage_scale: f64) -> f64 {
+        match self {
+            Self::Number(n) => n / number_scale,
+            Self::Percentage(p) => p * percentage_scale,
+        }
+    }
+}
+
+/// Parse a color written as hex, or as a CSS `rgb()`, `rgba()`, `hsl()`,
+/// `hsla()` or `oklch()` function in either the comma-separated or the
+/// space-separated syntax.
+pub fn color(input: &str) -> Result<Rgba, Error> {
+    let trimmed = input.trim();
+    let Some((function, arguments)) = trimmed.strip_suffix(')').and_then(|s| s.split_once('('))
+    else {
+        return hex(trimmed);
+    };
+
+    let syntax = || Error::Syntax(input.to_string());
+    let (channels, alpha): (Vec<&str>, Option<&str>) = if arguments.contains(',') {
+        let mut parts: Vec<&str> = arguments.split(',').map(str::trim).collect();
+        let alpha = if parts.len() == 4 { parts.pop() } else { None };
+        (parts, alpha)
+    } else {
+        let (channels, alpha) = arguments
+            .split_once('/')
+            .map_or((arguments, None), |(c, a)| (c, Some(a.trim())));
+        (channels.split_whitespace().collect(), alpha)
+    };
+    let function = function.trim().to_ascii_lowercase();
+    // the position of the hue, the only component that may have an angle unit
+    let hue = match function.as_str() {
+        "rgb" | "rgba" => None,
+        "hsl" | "hsla" => Some(0),
+        "oklch" => Some(2),
+        _ => return Err(syntax()),
+    };
+    let [a, b, c] = <[&str; 3]>::try_from(channels).map_err(|_| syntax())?;
+    let (a, b, c) = (
+        Component::parse(input, a, hue == Some(0))?,
+        Component::parse(input, b, hue == Some(1))?,
+        Component::parse(input, c, hue == Some(2))?,
+    );
+    let alpha = alpha
+        .map(|value| Component::parse(input, value, false))
+        .transpose()?
+        .map_or(1.0, |alpha| alpha.resolve(1.0, 1.0));
+
+    let rgb = match function.as_str() {
+        "rgb" | "rgba" => [a, b, c].map(|channel| channel.resolve(255.0, 1.0)),
+        "hsl" | "hsla" => colorspace::hsl_to_srgb([
+            a.resolve(1.0, 360.0).rem_euclid(360.0),
+            b.resolve(100.0, 1.0).clamp(0.0, 1.0),
+            c.resolve(100.0, 1.0).clamp(0.0, 1.0),
+        ]),
+        // per CSS Color 4, 100% chroma is 0.4
+        _ => colorspace::oklch_to_srgb_gamut_mapped([
+            a.resolve(1.0, 1.0).clamp(0.0, 1.0),
+            b.resolve(1.0, 0.4).max(0.0),
+            c.resolve(1.0, 360.0).rem_euclid(360.0),
+        ]),
+    };
+
+    Ok((rgb, alpha))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_parses(input: &str, rgb: [u8; 3], alpha: f64) {
+        let (parsed, parsed_alpha) = color(input).expect("color parses");
+        let parsed = parsed.map(|c| (c * 255.0).round() as u8);
+        assert_eq!(parsed, rgb, "{input}");
+        assert!((parsed_alpha - alpha).abs() < 1e-3, "{input}");
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_parses("fff", [255, 255, 255], 1.0);
+        assert_parses("#f008", [255, 0, 0], 0.533);
+        assert_parses("#1e1e2e", [30, 30, 46], 1.0);
+        assert_parses("1e1e2e80", [30, 30, 46], 0.502);
+        assert!(color("#12345").is_err());
+        assert!(color("#ggg").is_err());
+    }
+
+    #[test]
+    fn parses_css_functions() {
+        assert_parses("rgb(30 30 46 / 50%)", [30, 30, 46], 0.5);
+        assert_parses("rgba(30, 30, 46, 0.5)", [30, 30, 46], 0.5);
+        assert_parses("rgb(100%, 0%, 0%)", [255, 0, 0], 1.0);
+        assert_parses("hsl(120deg 100% 50%)", [0, 255, 0], 1.0);
+        assert_parses("hsla(240, 100%, 50%, 0.25)", [0, 0, 255], 0.25);
+        assert_parses("oklch(62.8% 0.2577 29.23)", [255, 0, 0], 1.0);
+        assert!(color("rgb(1 2)").is_err());
+        assert!(color("lab(50% 0 0)").is_err());
+        assert!(color("rgb(1 2 three)").is_err());
+    }
+
+    #[test]
+    fn accepts_angles_only_for_hue() {
+        assert_parses("oklch(62.8% 0.2577 29.23deg)", [255, 0, 0], 1.0);
+        assert!(color("rgb(10deg 20 30)").is_err());
+        assert!(color("hsl(10 20deg 30%)").is_err());
+        assert!(color("oklch(50% 0.1deg 30)").is_err());
+        assert!(color("rgb(10 20 30 / 1deg)").is_err());
+    }
+}
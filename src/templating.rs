@@ -1,6 +1,11 @@
+use std::collections::HashMap;
+
 use indexmap::IndexMap;
 
-use crate::{filters, functions};
+use crate::{
+    filters, functions,
+    models::{Color, HexFormat},
+};
 
 /// Allows creation of a [`FilterExample`] with the following syntax:
 ///
@@ -42,13 +47,105 @@ macro_rules! filter_example {
     };
 }
 
-pub fn make_engine() -> tera::Tera {
+/// Fail if `args` has any argument not in `expected`, so that typos aren't
+/// silently ignored.
+pub(crate) fn reject_unknown_args(
+    args: &HashMap<String, tera::Value>,
+    expected: &[&str],
+) -> Result<(), tera::Error> {
+    let Some(unknown) = args.keys().find(|key| !expected.contains(&key.as_str())) else {
+        return Ok(());
+    };
+    Err(tera::Error::msg(if expected.is_empty() {
+        format!("unknown argument `{unknown}`, expected no arguments")
+    } else {
+        format!(
+            "unknown argument `{unknown}`, expected any of: {}",
+            expected.join(", ")
+        )
+    }))
+}
+
+/// Wrap a color-producing filter so its result is rendered with the
+/// template's hex format.
+fn color_filter(
+    filter: fn(&tera::Value, &HashMap<String, tera::Value>) -> tera::Result<Color>,
+    hex_format: &HexFormat,
+) -> impl tera::Filter {
+    let hex_format = hex_format.clone();
+    move |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+        Ok(tera::to_value(filter(value, args)?.formatted(&hex_format))?)
+    }
+}
+
+/// Wrap a color-producing function so its result is rendered with the
+/// template's hex format.
+fn color_function(
+    function: fn(&HashMap<String, tera::Value>) -> tera::Result<Color>,
+    hex_format: &HexFormat,
+) -> impl tera::Function {
+    let hex_format = hex_format.clone();
+    move |args: &HashMap<String, tera::Value>| {
+        Ok(tera::to_value(function(args)?.formatted(&hex_format))?)
+    }
+}
+
+/// As [`color_function`], for functions producing a list of colors.
+fn colors_function(
+    function: fn(&HashMap<String, tera::Value>) -> tera::Result<Vec<Color>>,
+    hex_format: &HexFormat,
+) -> impl tera::Function {
+    let hex_format = hex_format.clone();
+    move |args: &HashMap<String, tera::Value>| {
+        let colors: Vec<Color> = function(args)?
+            .into_iter()
+            .map(|c| c.formatted(&hex_format))
+            .collect();
+        Ok(tera::to_value(colors)?)
+    }
+}
+
+/// Build the Tera engine. Colors produced by filters and functions have their
+/// `hex` field rendered with `hex_format`, matching the palette colors.
+pub fn make_engine(hex_format: &HexFormat) -> tera::Tera {
     let mut tera = tera::Tera::default();
-    tera.register_filter("add", filters::add);
-    tera.register_filter("sub", filters::sub);
-    tera.register_filter("mod", filters::modify);
+    tera.register_filter("add", color_filter(filters::add, hex_format));
+    tera.register_filter("sub", color_filter(filters::sub, hex_format));
+    tera.register_filter("mod", color_filter(filters::modify, hex_format));
+    tera.register_filter(
+        "ensure_contrast",
+        color_filter(filters::ensure_contrast, hex_format),
+    );
+    tera.register_filter("simulate", color_filter(filters::simulate, hex_format));
+    tera.register_filter("flatten", color_filter(filters::flatten, hex_format));
+    tera.register_filter("invert", color_filter(filters::invert, hex_format));
+    tera.register_filter("complement", color_filter(filters::complement, hex_format));
+    tera.register_filter("grayscale", color_filter(filters::grayscale, hex_format));
+    tera.register_filter("luminance", filters::luminance);
+    let hex = hex_format.clone();
+    tera.register_filter(
+        "hex",
+        move |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+            filters::hex(value, args, &hex)
+        },
+    );
+    tera.register_filter("css_rgb", filters::css_rgb);
+    tera.register_filter("css_hsl", filters::css_hsl);
+    tera.register_filter("css_oklch", filters::css_oklch);
+    tera.register_filter("rgb_int", filters::rgb_int);
+    tera.register_filter("bgr_int", filters::bgr_int);
+    tera.register_filter("floats", filters::floats);
+    tera.register_filter("xterm256", filters::xterm256);
+    tera.register_filter("ansi16", filters::ansi16);
     tera.register_filter("urlencode_lzma", filters::urlencode_lzma);
-    tera.register_function("mix", functions::mix);
+    tera.register_function("mix", color_function(functions::mix, hex_format));
+    tera.register_function("gradient", colors_function(functions::gradient, hex_format));
+    tera.register_function("blend", color_function(functions::blend, hex_format));
+    tera.register_function("contrast", functions::contrast);
+    tera.register_function("readable", color_function(functions::readable, hex_format));
+    tera.register_function("delta_e", functions::delta_e);
+    tera.register_function("nearest", color_function(functions::nearest, hex_format));
+    tera.register_function("color", color_function(functions::color, hex_format));
     tera.register_function("if", functions::if_fn);
     tera.register_function("object", functions::object);
     tera
@@ -59,10 +156,71 @@ pub fn all_functions() -> Vec<Function> {
     vec![
         Function {
             name: "mix".to_string(),
-            description: "Mix two colors together".to_string(),
+            description: "Mix two colors together, optionally in a perceptual color space. Translucent colors are mixed with premultiplied alpha, as in CSS `color-mix()`".to_string(),
             examples: vec![
-                function_example!(mix(base=base, blend=red, amount=0.5) => "#804040"),
-                function_example!(mix(base=base, blend=red, amount=0.5) => "#804040"),
+                function_example!(mix(base=base, blend=red, amount=0.5) => "#e18097"),
+                function_example!(mix(base=base, blend=red, amount=0.5, space="oklab") => "#eb9395"),
+                function_example!(mix(base=red, blend=blue, amount=0.5, space="oklch", hue="longer") => "#1f8b00"),
+            ],
+        },
+        Function {
+            name: "gradient".to_string(),
+            description: "Create a list of evenly spaced colors from one color to another, optionally in a perceptual color space".to_string(),
+            examples: vec![
+                function_example!(gradient(from=red, to=blue, steps=3) => "[#d20f39, #783b97, #1e66f5]"),
+                function_example!(gradient(from=red, to=blue, steps=3, space="oklch") => "[#d20f39, #a933bf, #1e66f5]"),
+            ],
+        },
+        Function {
+            name: "blend".to_string(),
+            description: "Blend a color onto a base with a W3C blend mode (normal, multiply, screen, overlay, darken, lighten, color-dodge, color-burn, hard-light, soft-light, difference, exclusion, hue, saturation, color, luminosity) at an optional opacity".to_string(),
+            examples: vec![
+                function_example!(blend(base=base, top=blue, mode="multiply") => "#1c60eb"),
+                function_example!(blend(base=base, top=blue, mode="overlay", opacity=0.2) => "#edf0f7"),
+            ],
+        },
+        Function {
+            name: "contrast".to_string(),
+            description: "Calculate the WCAG 2.x contrast ratio between two colors".to_string(),
+            examples: vec![function_example!(contrast(a=text, b=base) => "7.06")],
+        },
+        Function {
+            name: "readable".to_string(),
+            description: "Pick the first candidate color with at least `min` contrast (default 4.5) against a background. It is an error if none qualify, unless `fallback=true` is given to pick the highest-contrast one instead".to_string(),
+            examples: vec![
+                function_example!(readable(bg=blue, candidates=[text, base], min=3) => "base"),
+                function_example!(readable(bg=surface0, candidates=[subtext0, text]) => "text"),
+                function_example!(readable(bg=base, candidates=[yellow, peach], min=21, fallback=true) => "peach"),
+            ],
+        },
+        Function {
+            name: "delta_e".to_string(),
+            description: "Calculate the perceptual difference between two colors, using CIE76 (76), CIE94 (94), CIEDE2000 (2000, default) or Oklab distance (ok)".to_string(),
+            examples: vec![
+                function_example!(delta_e(a=red, b=maroon) => "9.15"),
+                function_example!(delta_e(a=red, b=maroon, method="ok") => "0.0771"),
+            ],
+        },
+        Function {
+            name: "nearest".to_string(),
+            description: "Find the color in a flavor closest to the given color, optionally using a `delta_e` method. The flavor is a flavor object such as `flavor` or `flavors.mocha`, not its identifier".to_string(),
+            examples: vec![
+                // the macro only takes single-token values, so spell out the nested call
+                FunctionExample {
+                    inputs: IndexMap::from([
+                        ("value".to_string(), r##"color(value="#ff6a10")"##.to_string()),
+                        ("flavor".to_string(), "flavor".to_string()),
+                    ]),
+                    output: "peach".to_string(),
+                },
+            ],
+        },
+        Function {
+            name: "color".to_string(),
+            description: "Create a color from a 3, 4, 6 or 8 digit hex string, or a CSS `rgb()`, `hsl()` or `oklch()` string".to_string(),
+            examples: vec![
+                function_example!(color(value="#1e1e2e") => "1e1e2e"),
+                function_example!(color(value="rgb(30 30 46 / 50%)") => "1e1e2e80"),
             ],
         },
         Function {
@@ -86,30 +244,143 @@ pub fn all_functions() -> Vec<Function> {
 }
 
 #[must_use]
+#[allow(clippy::too_many_lines)]
 pub fn all_filters() -> Vec<Filter> {
     vec![
         Filter {
             name: "add".to_string(),
-            description: "Add a value to a color".to_string(),
+            description: "Add a value to a color, in HSL or Oklch space. Saturation, lightness and opacity are fractions from 0 to 1 (0.5) or percentage strings (\"50%\")".to_string(),
             examples: vec![
-                filter_example!(red | add(hue=30) => "#ff6666"),
-                filter_example!(red | add(saturation=0.5) => "#ff6666"),
+                filter_example!(red | add(hue=30) => "#d2470f"),
+                filter_example!(red | add(saturation=0.5) => "#e10030"),
+                filter_example!(red | add(hue=30, lightness=0.1) => "#ef5e25"),
+                filter_example!(red | add(hue=30, space="oklch") => "#b25200"),
             ],
         },
         Filter {
             name: "sub".to_string(),
-            description: "Subtract a value from a color".to_string(),
+            description: "Subtract a value from a color, in HSL or Oklch space. Saturation, lightness and opacity are fractions from 0 to 1 (0.5) or percentage strings (\"50%\")".to_string(),
             examples: vec![
-                filter_example!(red | sub(hue=30) => "#ff6666"),
-                filter_example!(red | sub(saturation=0.5) => "#ff6666"),
+                filter_example!(red | sub(hue=30) => "#d20f9b"),
+                filter_example!(red | sub(saturation=0.5) => "#9a4759"),
+                filter_example!(red | sub(lightness="5%") => "#ba0d33"),
+                filter_example!(red | sub(lightness=0.1, space="oklch") => "#a20028"),
             ],
         },
         Filter {
             name: "mod".to_string(),
-            description: "Modify a color".to_string(),
+            description: "Modify a color, in HSL or Oklch space. Saturation, lightness and opacity are fractions from 0 to 1 (0.5) or percentage strings (\"50%\")".to_string(),
+            examples: vec![
+                filter_example!(red | mod(lightness=0.5) => "#ee1141"),
+                filter_example!(red | mod(opacity=0.5) => "#d20f3980"),
+                filter_example!(red | mod(chroma=0.1, space="oklch") => "#a45859"),
+            ],
+        },
+        Filter {
+            name: "ensure_contrast".to_string(),
+            description: "Adjust a color's Oklch lightness until it has at least `ratio` contrast (default 4.5) against another color".to_string(),
+            examples: vec![
+                filter_example!(yellow | ensure_contrast(against=base) => "#9d6000"),
+                filter_example!(yellow | ensure_contrast(against=base, ratio=7) => "#754700"),
+            ],
+        },
+        Filter {
+            name: "simulate".to_string(),
+            description: "Simulate how a color appears with a color vision deficiency (none, protanopia, deuteranopia, tritanopia)".to_string(),
+            examples: vec![
+                filter_example!(red | simulate(cvd="deuteranopia") => "#847732"),
+                filter_example!(red | simulate(cvd="protanopia", severity=0.5) => "#a43b39"),
+            ],
+        },
+        Filter {
+            name: "flatten".to_string(),
+            description: "Composite a translucent color over a background, giving an opaque color if the background is opaque".to_string(),
+            examples: vec![filter_example!(surface0_translucent | flatten(over=base) => "#dee1e8")],
+        },
+        Filter {
+            name: "invert".to_string(),
+            description: "Invert a color's RGB channels, keeping its opacity".to_string(),
+            examples: vec![filter_example!(red | invert => "#2df0c6")],
+        },
+        Filter {
+            name: "complement".to_string(),
+            description: "Rotate a color's hue by 180 degrees in HSL or Oklch, keeping its lightness".to_string(),
+            examples: vec![
+                filter_example!(red | complement => "#0fd2a8"),
+                filter_example!(red | complement(space="oklch") => "#008287"),
+            ],
+        },
+        Filter {
+            name: "grayscale".to_string(),
+            description: "Convert a color to the gray with the same relative luminance".to_string(),
+            examples: vec![filter_example!(red | grayscale => "#6a6a6a")],
+        },
+        Filter {
+            name: "luminance".to_string(),
+            description: "Calculate the WCAG 2.x relative luminance of a color, from 0 to 1".to_string(),
+            examples: vec![filter_example!(red | luminance => "0.1434")],
+        },
+        Filter {
+            name: "hex".to_string(),
+            description: "Format a color as hex using the template's hex options, optionally overriding the alpha layout: suffix, prefix, always (as a suffix), always-prefix, or never".to_string(),
             examples: vec![
-                filter_example!(red | mod(lightness=0.5) => "#ff6666"),
-                filter_example!(red | mod(opacity=0.5) => "#ff6666"),
+                filter_example!(red | hex => "d20f39"),
+                filter_example!(red | hex(alpha="always-prefix") => "ffd20f39"),
+            ],
+        },
+        Filter {
+            name: "css_rgb".to_string(),
+            description: "Format a color as CSS `rgb()`, or `rgba()` if it is translucent".to_string(),
+            examples: vec![
+                filter_example!(red | css_rgb => "rgb(210, 15, 57)"),
+                filter_example!(red_translucent | css_rgb => "rgba(210, 15, 57, 0.5)"),
+            ],
+        },
+        Filter {
+            name: "css_hsl".to_string(),
+            description: "Format a color as CSS `hsl()`, or `hsla()` if it is translucent".to_string(),
+            examples: vec![filter_example!(red | css_hsl => "hsl(347, 87%, 44%)")],
+        },
+        Filter {
+            name: "css_oklch".to_string(),
+            description: "Format a color as CSS `oklch()`, including alpha if it is translucent".to_string(),
+            examples: vec![filter_example!(red | css_oklch => "oklch(55.05% 0.2155 19.81)")],
+        },
+        Filter {
+            name: "rgb_int".to_string(),
+            description: "Convert a color to a 0xRRGGBB integer, or 0xRRGGBBAA with `alpha=true`".to_string(),
+            examples: vec![
+                filter_example!(red | rgb_int => "13766457"),
+                filter_example!(red | rgb_int(alpha=true) => "3524213247"),
+            ],
+        },
+        Filter {
+            name: "bgr_int".to_string(),
+            description: "Convert a color to a 0x00BBGGRR integer, as used by Windows (ignores opacity)".to_string(),
+            examples: vec![filter_example!(red | bgr_int => "3739602")],
+        },
+        Filter {
+            name: "floats".to_string(),
+            description: "Convert a color to a list of channels from 0 to 1, with alpha if it is translucent or `alpha=true`".to_string(),
+            examples: vec![
+                filter_example!(red | floats => "[0.824, 0.059, 0.224]"),
+                filter_example!(red | floats(precision=2, alpha=true) => "[0.82, 0.06, 0.22, 1]"),
+            ],
+        },
+        Filter {
+            name: "xterm256".to_string(),
+            description: "Find the perceptually closest xterm 256-color index, optionally restricted to a subset: all, ansi, cube, grayscale, or extended (cube and grayscale)".to_string(),
+            examples: vec![
+                filter_example!(red | xterm256 => "160"),
+                filter_example!(text | xterm256(subset="grayscale") => "239"),
+            ],
+        },
+        Filter {
+            name: "ansi16".to_string(),
+            description: "Find the perceptually closest ANSI color index, optionally restricted to a subset: all, normal, or bright".to_string(),
+            examples: vec![
+                filter_example!(red | ansi16 => "1"),
+                filter_example!(red | ansi16(subset="bright") => "9"),
             ],
         },
         Filter {
@@ -117,8 +388,7 @@ pub fn all_filters() -> Vec<Filter> {
             description: "Serialize an object into a URL-safe string with LZMA compression"
                 .to_string(),
             examples: vec![
-                filter_example!(red | urlencode_lzma => "#ff6666"),
-                filter_example!(red | urlencode_lzma => "#ff6666"),
+                filter_example!(red | urlencode_lzma => "XQAAgADuAAAAAAAAAABFKYgmPFEHYnjutieWqAoF3MeOzA2vW-S0g0mp592qNBZG4UA7ednmOvrMcHu0UY98l_GqECPp3lmedv3N3CCJf_0NHHJ-rW27rV-_dsKINDxrfy2BvtPiZgQiJOPswBuMbN45Ha9U_-Ifhj5qt1vMglEMOARPAV6Qa7pDgZ_CC3VzYKbs94ZVkDKTpYxUBFrNPIHwB8cwP3ycaRF2s_LOCDidii3kDRt8zginOROz1BTQ79L-wWdEmaZ4Senpj_x14UzUvgA="),
             ],
         },
     ]
@@ -153,6 +423,116 @@ pub struct FilterExample {
 
 #[cfg(test)]
 mod tests {
+    use super::{all_filters, all_functions};
+    use crate::models::{build_palette, default_palette, HexFormat};
+
+    /// Render `expression` against latte as JSON. The examples' translucent
+    /// stand-ins are defined as half-transparent versions of their colors.
+    fn render_example(expression: &str) -> serde_json::Value {
+        let palette = build_palette(&default_palette(), &HexFormat::default(), None)
+            .expect("default palette builds");
+        let latte = &palette.flavors["latte"];
+        let mut ctx = tera::Context::new();
+        ctx.insert("flavors", &palette.flavors);
+        ctx.insert("flavor", latte);
+        for (identifier, color) in &latte.colors {
+            ctx.insert(identifier, color);
+        }
+        let template = format!(
+            "{{% set red_translucent = red | mod(opacity=0.5) %}}\
+             {{% set surface0_translucent = surface0 | mod(opacity=0.5) %}}\
+             {{{{ {expression} | json_encode() | safe }}}}"
+        );
+        let rendered = super::make_engine(&HexFormat::default())
+            .render_str(&template, &ctx)
+            .unwrap_or_else(|e| panic!("`{expression}` fails to render: {e:?}"));
+        serde_json::from_str(&rendered).expect("json_encode gives valid JSON")
+    }
+
+    /// Whether `actual` is what `expected` describes in the examples'
+    /// shorthand: colors as their hex or identifier, numbers rounded to the
+    /// precision shown, and lists and objects written without quotes.
+    fn example_matches(expected: &str, actual: &serde_json::Value) -> bool {
+        use serde_json::Value;
+
+        let items = |open: char, close: char| {
+            expected
+                .strip_prefix(open)
+                .and_then(|e| e.strip_suffix(close))
+                .map(|e| e.split(", ").filter(|i| !i.is_empty()).collect::<Vec<_>>())
+        };
+        match actual {
+            Value::Object(color) if color.contains_key("hex") => {
+                color["hex"] == expected.trim_start_matches('#') || color["identifier"] == expected
+            }
+            Value::Object(object) => items('{', '}').is_some_and(|items| {
+                items.len() == object.len()
+                    && items.iter().all(|item| {
+                        item.split_once(": ").is_some_and(|(key, value)| {
+                            object
+                                .get(key)
+                                .is_some_and(|actual| example_matches(value, actual))
+                        })
+                    })
+            }),
+            Value::Array(list) => items('[', ']').is_some_and(|items| {
+                items.len() == list.len()
+                    && items
+                        .iter()
+                        .zip(list)
+                        .all(|(item, actual)| example_matches(item, actual))
+            }),
+            Value::Number(n) => expected.parse::<f64>().is_ok_and(|e| {
+                let decimals = expected.split_once('.').map_or(0, |(_, d)| d.len());
+                let tolerance = 0.5 / 10f64.powi(i32::try_from(decimals).unwrap_or(i32::MAX));
+                n.as_f64()
+                    .is_some_and(|n| (n - e).abs() <= tolerance + f64::EPSILON)
+            }),
+            Value::String(s) => s == expected,
+            other => serde_json::from_str::<Value>(expected).is_ok_and(|e| e == *other),
+        }
+    }
+
+    #[test]
+    fn examples_match_their_output_on_latte() {
+        let args = |inputs: &indexmap::IndexMap<String, String>| {
+            inputs
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let functions = all_functions().into_iter().flat_map(|function| {
+            function.examples.into_iter().map(move |example| {
+                (
+                    format!("{}({})", function.name, args(&example.inputs)),
+                    example.output,
+                )
+            })
+        });
+        let filters = all_filters().into_iter().flat_map(|filter| {
+            filter.examples.into_iter().map(move |example| {
+                let call = if example.inputs.is_empty() {
+                    filter.name.clone()
+                } else {
+                    format!("{}({})", filter.name, args(&example.inputs))
+                };
+                (format!("{} | {call}", example.value), example.output)
+            })
+        });
+
+        let mismatches = functions
+            .chain(filters)
+            .filter_map(|(expression, expected)| {
+                let actual = render_example(&expression);
+                (!example_matches(&expected, &actual)).then(|| {
+                    format!("`{expression}` is documented as {expected} but gives {actual}")
+                })
+            })
+            .collect::<Vec<_>>();
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+    }
+
     #[test]
     fn function_example_with_single_arg() {
         let example = function_example!(mix(base=base) => "#804040");
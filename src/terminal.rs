@@ -0,0 +1,157 @@
+//! Mapping colors onto the fixed palettes of 256- and 16-color terminals.
+
+use std::ops::RangeInclusive;
+
+use crate::colorspace::srgb_to_oklab;
+
+/// The default xterm values of the 16 ANSI colors.
+///
+/// Most terminals let users theme these, so they are only an approximation of
+/// what will actually be displayed.
+const ANSI: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0xcd, 0x00, 0x00],
+    [0x00, 0xcd, 0x00],
+    [0xcd, 0xcd, 0x00],
+    [0x00, 0x00, 0xee],
+    [0xcd, 0x00, 0xcd],
+    [0x00, 0xcd, 0xcd],
+    [0xe5, 0xe5, 0xe5],
+    [0x7f, 0x7f, 0x7f],
+    [0xff, 0x00, 0x00],
+    [0x00, 0xff, 0x00],
+    [0xff, 0xff, 0x00],
+    [0x5c, 0x5c, 0xff],
+    [0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+/// The channel levels of the 6x6x6 color cube.
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+/// The subset of the xterm 256-color palette to match against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Xterm256Subset {
+    /// Every index from 0 to 255.
+    #[default]
+    All,
+    /// Only the 16 ANSI colors, 0 to 15.
+    Ansi,
+    /// Only the 6x6x6 color cube, 16 to 231.
+    Cube,
+    /// Only the grayscale ramp, 232 to 255.
+    Grayscale,
+    /// The cube and grayscale ramp, 16 to 255. These are not affected by the
+    /// terminal's theme.
+    Extended,
+}
+
+impl Xterm256Subset {
+    const fn indices(self) -> RangeInclusive<u8> {
+        match self {
+            Self::All => 0..=255,
+            Self::Ansi => 0..=15,
+            Self::Cube => 16..=231,
+            Self::Grayscale => 232..=255,
+            Self::Extended => 16..=255,
+        }
+    }
+}
+
+/// The subset of the 16 ANSI colors to match against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ansi16Subset {
+    /// Every index from 0 to 15.
+    #[default]
+    All,
+    /// Only the normal colors, 0 to 7.
+    Normal,
+    /// Only the bright colors, 8 to 15.
+    Bright,
+}
+
+impl Ansi16Subset {
+    const fn indices(self) -> RangeInclusive<u8> {
+        match self {
+            Self::All => 0..=15,
+            Self::Normal => 0..=7,
+            Self::Bright => 8..=15,
+        }
+    }
+}
+
+/// The RGB value of an xterm 256-color palette index.
+#[must_use]
+pub fn xterm256_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..=15 => ANSI[usize::from(index)],
+        16..=231 => {
+            let i = usize::from(index - 16);
+            [i / 36, (i / 6) % 6, i % 6].map(|level| CUBE_LEVELS[level])
+        }
+        232..=255 => {
+            let gray = 8 + (index - 232) * 10;
+            [gray; 3]
+        }
+    }
+}
+
+/// Find the index whose color is perceptually closest to `rgb`, measured as
+/// the euclidean distance in Oklab.
+fn nearest(rgb: [f64; 3], indices: RangeInclusive<u8>) -> u8 {
+    let target = srgb_to_oklab(rgb);
+    let distance = |index: &u8| {
+        let lab = srgb_to_oklab(xterm256_rgb(*index).map(|c| f64::from(c) / 255.0));
+        (0..3).map(|i| (lab[i] - target[i]).powi(2)).sum::<f64>()
+    };
+    indices
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or_default()
+}
+
+/// The closest xterm 256-color palette index to an sRGB color.
+#[must_use]
+pub fn nearest_xterm256(rgb: [f64; 3], subset: Xterm256Subset) -> u8 {
+    nearest(rgb, subset.indices())
+}
+
+/// The closest ANSI color index to an sRGB color.
+#[must_use]
+pub fn nearest_ansi16(rgb: [f64; 3], subset: Ansi16Subset) -> u8 {
+    nearest(rgb, subset.indices())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn srgb(rgb: [u8; 3]) -> [f64; 3] {
+        rgb.map(|c| f64::from(c) / 255.0)
+    }
+
+    #[test]
+    fn palette_colors_map_to_themselves() {
+        for index in 16..=255 {
+            let rgb = srgb(xterm256_rgb(index));
+            assert_eq!(nearest_xterm256(rgb, Xterm256Subset::Extended), index);
+        }
+        for index in 0..=15 {
+            let rgb = srgb(xterm256_rgb(index));
+            assert_eq!(nearest_ansi16(rgb, Ansi16Subset::All), index);
+        }
+    }
+
+    #[test]
+    fn subsets_are_respected() {
+        let red = [1.0, 0.0, 0.0];
+        assert_eq!(nearest_xterm256(red, Xterm256Subset::All), 9);
+        assert_eq!(nearest_xterm256(red, Xterm256Subset::Cube), 196);
+        assert_eq!(nearest_ansi16(red, Ansi16Subset::Normal), 1);
+        assert!(Xterm256Subset::Grayscale
+            .indices()
+            .contains(&nearest_xterm256(red, Xterm256Subset::Grayscale)));
+    }
+}
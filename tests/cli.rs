@@ -1,30 +1,802 @@
 use assert_cmd::Command;
 
+/// Run whiskers with the given arguments, expecting success, and return its output.
+fn run(args: &[&str]) -> String {
+    let mut cmd = Command::cargo_bin("whiskers2").expect("binary exists");
+    let assert = cmd.args(args).assert();
+    String::from_utf8(assert.success().get_output().stdout.clone()).expect("output is valid utf-8")
+}
+
+/// Render a template from stdin with extra arguments, expecting success, and
+/// return its output.
+fn render_with(args: &[&str], template: &str) -> String {
+    let mut cmd = Command::cargo_bin("whiskers2").expect("binary exists");
+    let assert = cmd.arg("-").args(args).write_stdin(template).assert();
+    String::from_utf8(assert.success().get_output().stdout.clone()).expect("output is valid utf-8")
+}
+
+/// Render a template from stdin for a single flavor, expecting success, and
+/// return its output.
+fn render(flavor: &str, template: &str) -> String {
+    render_with(&["-f", flavor], template)
+}
+
+/// Render a template from stdin with extra arguments, expecting failure, and
+/// return the error output.
+fn render_err(args: &[&str], template: &str) -> String {
+    let mut cmd = Command::cargo_bin("whiskers2").expect("binary exists");
+    let assert = cmd.arg("-").args(args).write_stdin(template).assert();
+    String::from_utf8(assert.failure().get_output().stderr.clone()).expect("output is valid utf-8")
+}
+
+/// The lines of `output` in sorted order, for multi-output runs whose files
+/// are written in no particular order.
+fn sorted_lines(output: &str) -> Vec<&str> {
+    let mut lines = output.lines().collect::<Vec<_>>();
+    lines.sort_unstable();
+    lines
+}
+
 #[test]
 fn test_single() {
-    let mut cmd = Command::cargo_bin("whiskers2").expect("binary exists");
-    let assert = cmd
-        .args(["tests/fixtures/single/single.j2", "-f", "latte"])
-        .assert();
-    assert
-        .success()
-        .stdout(include_str!("fixtures/single/single.md"));
+    assert_eq!(
+        run(&["tests/fixtures/single/single.j2", "-f", "latte"]),
+        include_str!("fixtures/single/single.md")
+    );
 }
 
 #[test]
 fn test_multi() {
-    let mut cmd = Command::cargo_bin("whiskers2").expect("binary exists");
-    let assert = cmd.args(["tests/fixtures/multi/multi.j2"]).assert();
-    assert
-        .success()
-        .stdout(include_str!("fixtures/multi/multi.md"));
+    assert_eq!(
+        run(&["tests/fixtures/multi/multi.j2"]),
+        include_str!("fixtures/multi/multi.md")
+    );
 }
 
 #[test]
 fn test_multifile_render() {
+    run(&["--dry-run", "tests/fixtures/multifile.j2"]);
+}
+
+#[test]
+fn test_oklch_adjustment() {
+    let output = render(
+        "latte",
+        r#"{% set c = red | sub(lightness=0.1, space="oklch") %}{{ c.hex }}"#,
+    );
+    assert_eq!(output, "a20028");
+}
+
+#[test]
+fn test_mix_spaces() {
+    let output = render(
+        "mocha",
+        concat!(
+            r#"{% for s in ["srgb", "linear-srgb", "oklab", "oklch"] %}"#,
+            r#"{% set c = mix(base=base, blend=red, amount=0.5, space=s) %}{{ c.hex }} "#,
+            "{% endfor %}"
+        ),
+    );
+    assert_eq!(output, "89546b b4677e 815268 79537d ");
+}
+
+#[test]
+fn test_contrast_and_readable() {
+    let output = render(
+        "latte",
+        concat!(
+            "{{ contrast(a=text, b=base) | round(precision=2) }} ",
+            "{% set c = readable(bg=blue, candidates=[text, base], min=3) %}{{ c.identifier }} ",
+            "{% set c = readable(bg=base, candidates=[yellow, peach], min=21, fallback=true) %}{{ c.identifier }}",
+        ),
+    );
+    assert_eq!(output, "7.06 base peach");
+
+    let stderr = render_err(
+        &["-f", "latte"],
+        "{{ readable(bg=base, candidates=[yellow, peach], min=21) }}",
+    );
+    assert!(stderr.contains("the best is `peach` at"), "{stderr}");
+}
+
+#[test]
+fn test_ensure_contrast() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set c = yellow | ensure_contrast(against=base) %}{{ c.hex }} ",
+            "{% set c = blue | ensure_contrast(against=base, ratio=3) %}{{ c.hex }}",
+        ),
+    );
+    assert_eq!(output, "9d6000 1e66f5");
+
+    render_err(
+        &["-f", "latte"],
+        "{{ base | ensure_contrast(against=surface2, ratio=21) }}",
+    );
+
+    let stderr = render_err(
+        &["-f", "latte"],
+        "{{ yellow | ensure_contrast(against=base, min=7) }}",
+    );
+    assert!(stderr.contains("unknown argument `min`"), "{stderr}");
+}
+
+#[test]
+fn test_accessibility_report() {
+    let output = run(&[
+        "--accessibility-report",
+        "-f",
+        "mocha",
+        "--color-overrides",
+        r#"{"mocha": {"base": "ffffff"}}"#,
+    ]);
+    let report: serde_json::Value = serde_json::from_str(&output).expect("report is valid JSON");
+    let pairs = report["mocha"].as_array().expect("mocha is reported");
+    let text_on_base = pairs
+        .iter()
+        .find(|p| p["foreground"] == "text" && p["background"] == "base")
+        .expect("text on base is reported");
+    assert_eq!(text_on_base["aa"], false);
+    assert!(report.get("latte").is_none());
+}
+
+#[test]
+fn test_simulate_cvd() {
+    let output = render(
+        "latte",
+        concat!(
+            r#"{% set c = red | simulate(cvd="deuteranopia") %}{{ c.hex }} "#,
+            r#"{% set c = red | simulate(cvd="none") %}{{ c.hex }}"#,
+        ),
+    );
+    assert_eq!(output, "847732 d20f39");
+
+    let stderr = render_err(
+        &["-f", "latte"],
+        r#"{{ red | simulate(cvd="protanopia", severty=0.5) }}"#,
+    );
+    assert!(stderr.contains("unknown argument `severty`"), "{stderr}");
+}
+
+#[test]
+fn test_gradient() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% for c in gradient(from=red, to=blue, steps=3) %}{{ c.hex }} {% endfor %}",
+            r#"{% for c in gradient(from=red, to=blue, steps=3, space="linear-srgb") %}{{ c.hex }} {% endfor %}"#,
+            r#"{% for c in gradient(from=red, to=blue, steps=3, space="oklch") %}{{ c.hex }} {% endfor %}"#,
+        ),
+    );
+    assert_eq!(
+        output,
+        "d20f39 783b97 1e66f5 d20f39 9b4ab8 1e66f5 d20f39 a933bf 1e66f5 "
+    );
+}
+
+#[test]
+fn test_adjustments_apply_every_argument() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set a = red | add(hue=30) | add(lightness=0.1) %}{{ a.hex }} ",
+            "{% set b = red | add(lightness=0.1, hue=30) %}{{ b.hex }}",
+        ),
+    );
+    assert_eq!(output, "ef5e25 ef5e25");
+
+    render_err(&["-f", "latte"], "{{ red | add(hue=30, lightnes=10) }}");
+}
+
+#[test]
+fn test_adjustment_units() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set a = red | add(saturation=0.5) %}{{ a.hex }} ",
+            r#"{% set b = red | add(saturation="50%") %}{{ b.hex }} "#,
+            "{% set c = red | sub(lightness=1) %}{{ c.hex }} ",
+            r#"{% set d = red | sub(lightness="1%") %}{{ d.hex }}"#,
+        ),
+    );
+    assert_eq!(output, "e10030 e10030 000000 cd0f38");
+
+    for template in [
+        "{{ red | mod(lightness=150) }}",
+        "{{ red | sub(lightness=2) }}",
+        "{{ red | mod(lightness=-0.1) }}",
+        r#"{{ red | mod(opacity="half") }}"#,
+    ] {
+        render_err(&["-f", "latte"], template);
+    }
+
+    let stderr = render_err(&["-f", "latte"], "{{ red | sub(lightness=20) }}");
+    assert!(
+        stderr.contains(r#"got 20; use "20%" or 0.2 instead"#),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_chained_adjustments_are_reversible() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set c = red | add(hue=30) | add(lightness=0.1) | sub(lightness=0.1) | sub(hue=30) %}",
+            "{{ c.hex }} {{ c.hsl.h | round(precision=6) }} {{ c.float.r | round(precision=6) }}",
+        ),
+    );
+    assert_eq!(output, "d20f39 347.076923 0.823529");
+}
+
+#[test]
+fn test_format_filters() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set t = red | mod(opacity=0.5) %}",
+            "{{ red | css_rgb }}; {{ t | css_rgb }}; {{ red | css_hsl }}; {{ t | css_hsl }}; ",
+            "{{ red | css_oklch }}; {{ t | css_oklch }}; ",
+            "{{ red | rgb_int }} {{ t | rgb_int }} {{ t | rgb_int(alpha=true) }} {{ red | bgr_int }} ",
+            "{{ red | floats }} {{ red | floats(precision=2, alpha=true) }}",
+        ),
+    );
+    assert_eq!(
+        output,
+        concat!(
+            "rgb(210, 15, 57); rgba(210, 15, 57, 0.5); hsl(347, 87%, 44%); hsla(347, 87%, 44%, 0.5); ",
+            "oklch(55.05% 0.2155 19.81); oklch(55.05% 0.2155 19.81 / 0.5); ",
+            "13766457 13766457 3524213120 3739602 ",
+            "[0.824, 0.059, 0.224] [0.82, 0.06, 0.22, 1]",
+        )
+    );
+
+    for template in [
+        "{{ red | css_rgb(alpha=true) }}",
+        "{{ red | css_hsl(precision=1) }}",
+        "{{ red | css_oklch(precision=1) }}",
+        "{{ red | rgb_int(aplha=true) }}",
+        "{{ red | bgr_int(alpha=true) }}",
+        "{{ red | floats(precison=1) }}",
+    ] {
+        let stderr = render_err(&["-f", "latte"], template);
+        assert!(stderr.contains("unknown argument"), "{stderr}");
+    }
+}
+
+#[test]
+fn test_hex_format() {
+    let output = render(
+        "latte",
+        concat!(
+            "---\n",
+            "whiskers:\n",
+            "  version: 2.0.0\n",
+            "  hex_prefix: \"#\"\n",
+            "  capitalize_hex: true\n",
+            "  hex_format: always-prefix\n",
+            "---\n",
+            "{% set t = red | mod(opacity=0.5) %}",
+            r#"{{ red.hex }} {{ t.hex }} {{ t | hex(alpha="suffix") }} {{ red | hex(alpha="always") }} {{ t | hex(alpha="never") }}"#,
+        ),
+    );
+    assert_eq!(output, "#FFD20F39 #80D20F39 #D20F3980 #D20F39FF #D20F39");
+
+    let stderr = render_err(&["-f", "latte"], "{{ red | hex(prefix=true) }}");
+    assert!(stderr.contains("unknown argument `prefix`"), "{stderr}");
+}
+
+#[test]
+fn test_hex_format_survives_operations() {
+    let output = render(
+        "latte",
+        concat!(
+            "---\n",
+            "whiskers:\n",
+            "  version: 2.0.0\n",
+            "  hex_prefix: \"#\"\n",
+            "  capitalize_hex: true\n",
+            "---\n",
+            "{% set a = red | add(hue=30) | sub(lightness=0.1) %}",
+            "{% set b = mix(base=base, blend=red, amount=0.5) %}",
+            "{% set c = readable(bg=base, candidates=[text, base]) %}",
+            "{{ a.hex }} {{ b.hex }} {{ c.hex }} ",
+            "{% for g in gradient(from=red, to=blue, steps=2) %}{{ g.hex }} {% endfor %}",
+            "{{ a | hex }}",
+        ),
+    );
+    for hex in output.split_whitespace() {
+        assert!(hex.starts_with('#'), "{hex} is missing the prefix");
+        assert_eq!(hex, hex.to_uppercase(), "{hex} is not capitalized");
+    }
+}
+
+#[test]
+fn test_terminal_colors() {
+    let output = render(
+        "latte",
+        concat!(
+            "{{ red | xterm256 }} {{ blue | xterm256 }} ",
+            r#"{{ text | xterm256(subset="grayscale") }} "#,
+            r#"{{ red | ansi16 }} {{ red | ansi16(subset="bright") }}"#,
+        ),
+    );
+    assert_eq!(output, "160 27 239 1 9");
+
+    render_err(&["-f", "latte"], r#"{{ red | ansi16(subset="cube") }}"#);
+    for template in [
+        r#"{{ red | ansi16(subest="bright") }}"#,
+        r#"{{ red | xterm256(subest="cube") }}"#,
+    ] {
+        let stderr = render_err(&["-f", "latte"], template);
+        assert!(stderr.contains("unknown argument `subest`"), "{stderr}");
+    }
+}
+
+#[test]
+fn test_color_literals() {
+    let output = render(
+        "latte",
+        concat!(
+            r##"{% set a = color(value="#fff") %}{{ a.hex }} "##,
+            r#"{% set b = color(value="rgb(30 30 46 / 50%)") %}{{ b.hex }} "#,
+            r#"{% set c = color(value="hsl(0 100% 50%)") | add(hue=120) %}{{ c.hex }} "#,
+            r#"{% set d = mix(base=color(value="oklch(70% 0.1 200)"), blend=red, amount=0.5) %}{{ d.hex }}"#,
+        ),
+    );
+    assert_eq!(output, "ffffff 1e1e2e80 00ff00 896078");
+
+    for template in [
+        r##"{{ color(value="#12345") }}"##,
+        r#"{{ color(value="lab(50% 0 0)") }}"#,
+        "{{ color(value=12) }}",
+    ] {
+        render_err(&["-f", "latte"], template);
+    }
+}
+
+#[test]
+fn test_invert_complement_grayscale() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set a = red | mod(opacity=0.5) | invert %}{{ a.hex }} ",
+            "{% set b = red | complement %}{{ b.hex }} ",
+            r#"{% set c = red | complement(space="oklch") %}{{ c.hex }} "#,
+            "{% set d = red | grayscale %}{{ d.hex }} ",
+            "{{ red | luminance | round(precision=4) }} {{ d | luminance | round(precision=4) }}",
+        ),
+    );
+    assert_eq!(output, "2df0c680 0fd2a8 008287 6a6a6a 0.1434 0.1434");
+
+    for template in [
+        r#"{{ red | invert(space="oklch") }}"#,
+        r#"{{ red | complement(spcae="oklch") }}"#,
+        r#"{{ red | grayscale(spcae="oklch") }}"#,
+        "{{ red | luminance(precision=2) }}",
+    ] {
+        let stderr = render_err(&["-f", "latte"], template);
+        assert!(stderr.contains("unknown argument"), "{stderr}");
+    }
+}
+
+#[test]
+fn test_flatten() {
+    let output = render(
+        "mocha",
+        concat!(
+            "{% set a = surface0 | mod(opacity=0.4) | flatten(over=base) %}{{ a.hex }} ",
+            "{% set b = red | flatten(over=base) %}{{ b.hex }} ",
+            "{% set c = red | mod(opacity=0.5) | flatten(over=base | mod(opacity=0.5)) %}{{ c.hex }}",
+        ),
+    );
+    assert_eq!(output, "262637 f38ba8 ac677fbf");
+
+    let stderr = render_err(
+        &["-f", "mocha"],
+        "{{ red | flatten(over=base, opacity=0.2) }}",
+    );
+    assert!(stderr.contains("unknown argument `opacity`"), "{stderr}");
+}
+
+#[test]
+fn test_alpha_semantics() {
+    let output = render(
+        "latte",
+        concat!(
+            "{% set t = red | mod(opacity=0.4) %}{{ t.opacity }} {{ t.float.a }} ",
+            "{% set a = t | add(hue=30, lightness=0.1) %}{{ a.opacity }} ",
+            "{% set b = t | sub(saturation=0.1) %}{{ b.opacity }} ",
+            r#"{% set c = t | mod(chroma=0.1, space="oklch") %}{{ c.opacity }} "#,
+            "{% set d = t | add(opacity=0.2) %}{{ d.opacity }} ",
+            "{% set m = mix(base=t, blend=blue, amount=0.5) %}{{ m.hex }} ",
+            "{% set n = mix(base=red, blend=blue | mod(opacity=0), amount=0.5) %}{{ n.hex }} ",
+            "{{ t | css_rgb }}",
+        ),
+    );
+    assert_eq!(
+        output,
+        "102 0.4 102 102 102 153 514dbfb3 d20f3980 rgba(210, 15, 57, 0.4)"
+    );
+}
+
+#[test]
+fn test_delta_e_and_nearest() {
+    let output = render(
+        "mocha",
+        concat!(
+            "{{ delta_e(a=red, b=maroon) | round(precision=2) }} ",
+            "{{ delta_e(a=red, b=maroon, method=76) | round(precision=2) }} ",
+            r#"{{ delta_e(a=red, b=red, method="ok") }} "#,
+            r##"{% set n = nearest(value=color(value="#89b4fa"), flavor=flavors.latte) %}{{ n.identifier }} "##,
+            r##"{% set n = nearest(value=color(value="#1e1e2e"), flavor=flavor) %}{{ n.identifier }}"##,
+        ),
+    );
+    assert_eq!(output, "6.25 14.11 0 lavender base");
+
+    render_err(
+        &["-f", "mocha"],
+        r#"{{ delta_e(a=red, b=maroon, method="2001") }}"#,
+    );
+
+    let stderr = render_err(
+        &["-f", "mocha"],
+        r#"{{ nearest(value=red, flavor="latte") }}"#,
+    );
+    assert!(
+        stderr.contains(r#"use `flavors.latte` instead of "latte""#),
+        "{stderr}"
+    );
+    let stderr = render_err(&["-f", "mocha"], "{{ nearest(value=red, flavor=red) }}");
+    assert!(
+        stderr.contains("flavor must be a flavor object"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_blend() {
+    let output = render(
+        "latte",
+        concat!(
+            r#"{% for m in ["normal", "multiply", "screen", "overlay", "soft-light", "luminosity"] %}"#,
+            "{% set c = blend(base=base, top=blue, mode=m, opacity=0.2) %}{{ c.hex }} ",
+            "{% endfor %}",
+            r#"{% set c = blend(base=base, top=blue, mode="multiply") %}{{ c.hex }}"#,
+        ),
+    );
+    assert_eq!(output, "c5d5f5 c5d4f3 eff2f7 edf0f7 edf0f6 d2d4d8 1c60eb");
+
+    for template in [
+        r#"{{ blend(base=base, top=blue, mode="burn") }}"#,
+        "{{ blend(base=base, top=blue, opacity=2) }}",
+    ] {
+        render_err(&["-f", "latte"], template);
+    }
+}
+
+#[test]
+fn test_functions_reject_unknown_args() {
+    for template in [
+        r#"{{ mix(base=base, blend=red, amount=0.5, spcae="oklch") }}"#,
+        "{{ gradient(from=red, to=blue, steps=3, step=2) }}",
+        r#"{{ blend(base=base, top=blue, mdoe="multiply") }}"#,
+        "{{ contrast(a=text, b=base, c=red) }}",
+        r#"{{ delta_e(a=red, b=maroon, metod="ok") }}"#,
+        "{{ nearest(value=red, flavor=flavor, fallback=true) }}",
+        "{{ readable(bg=base, candidates=[text], minimum=3) }}",
+        r#"{{ color(hex="fff") }}"#,
+    ] {
+        let stderr = render_err(&["-f", "latte"], template);
+        assert!(stderr.contains("unknown argument"), "{stderr}");
+    }
+}
+
+#[test]
+fn test_custom_colors() {
+    let overrides = r#"{
+        "all": {"brand": {"hex": "ff8800", "name": "Brand", "accent": true}, "logo": {"hex": "000000"}},
+        "mocha": {"brand": "ffaa00", "red": {"hex": "ff0000", "name": "Fire"}}
+    }"#;
+
+    let output = render_with(
+        &["-f", "mocha", "--color-overrides", overrides],
+        concat!(
+            "{{ brand.hex }} {{ brand.name }} {{ brand.accent }} {{ logo.accent }} ",
+            "{{ red.name }} {{ red.hex }} {{ flavors.latte.colors.brand.hex }} ",
+            "{% for id, c in flavor.colors %}{% if c.accent %}{{ id }} {% endif %}{% endfor %}",
+        ),
+    );
+    assert_eq!(
+        output,
+        concat!(
+            "ffaa00 Brand true false Fire ff0000 ff8800 ",
+            "rosewater flamingo pink mauve red maroon peach yellow green teal sky sapphire blue lavender brand ",
+        )
+    );
+
+    let output = run(&[
+        "--dry-run",
+        "tests/fixtures/custom/accents.j2",
+        "--color-overrides",
+        overrides,
+    ]);
+    assert_eq!(output.lines().count(), 4 * 15);
+    assert!(output.contains("mocha-brand.txt"));
+}
+
+#[test]
+fn test_custom_color_overridden_in_another_flavor() {
+    let overrides = r#"{
+        "latte": {"glow": {"hex": "ff00ff", "name": "Glow", "accent": true}},
+        "mocha": {"glow": "00ff00"}
+    }"#;
+    let output = render_with(
+        &["--color-overrides", overrides],
+        concat!(
+            "{% set l = flavors.latte.colors.glow %}{% set m = flavors.mocha.colors.glow %}",
+            "{{ l.hex }} {{ m.hex }} {{ m.name }} {{ m.accent }} ",
+            "{{ flavors.frappe.colors.glow is defined }}",
+        ),
+    );
+    assert_eq!(output, "ff00ff 00ff00 Glow true false");
+}
+
+#[test]
+fn test_color_override_hex_forms() {
+    let output = render_with(
+        &[
+            "-f",
+            "latte",
+            "--color-overrides",
+            r##"{"all": {"base": "fff", "red": "#ff000080"}, "latte": {"blue": "#00f8"}}"##,
+        ],
+        "{{ base.hex }} {{ red.hex }} {{ blue.hex }}",
+    );
+    assert_eq!(output, "ffffff ff000080 0000ff88");
+}
+
+#[test]
+fn test_invalid_color_overrides() {
+    for (overrides, message) in [
+        (
+            r#"{"all": {"mauv": "ff00ff"}}"#,
+            "Unknown color `mauv` in `all` color overrides",
+        ),
+        (
+            r#"{"mocha": {"lavendar": "ff00ff"}}"#,
+            "Unknown color `lavendar` in `mocha` color overrides",
+        ),
+        (
+            r#"{"mochaa": {"red": "ff0000"}}"#,
+            "Unknown flavor `mochaa` in color overrides",
+        ),
+        (
+            r##"{"frappe": {"red": "#12345"}}"##,
+            r##"Invalid hex color "#12345" for `red` in `frappe` color overrides"##,
+        ),
+        (
+            r#"{"all": {"red": "ff00zz"}}"#,
+            r#"Invalid hex color "ff00zz" for `red` in `all` color overrides"#,
+        ),
+        (
+            r#"{"all": {"glow": {"hex": "ff00ff", "acent": true}}}"#,
+            "unknown field `acent`, expected one of `hex`, `name`, `accent`",
+        ),
+        (
+            r#"{"all": {"red": 12}}"#,
+            "expected a hex string, or an object with `hex`, `name` and `accent` keys",
+        ),
+    ] {
+        let stderr = render_err(
+            &["--color-overrides", overrides],
+            "{{ flavors.latte.colors.red.hex }}",
+        );
+        assert!(stderr.contains(message), "{stderr}");
+    }
+}
+
+#[test]
+fn test_custom_palette() {
+    let output = run(&[
+        "--dry-run",
+        "tests/fixtures/palette/palette.j2",
+        "--palette",
+        "tests/fixtures/palette/palette.yaml",
+    ]);
+    assert_eq!(
+        sorted_lines(&output),
+        [
+            "Would write 6 bytes into espresso-blue.txt",
+            "Would write 6 bytes into espresso-red.txt",
+            "Would write 6 bytes into mocha-blue.txt",
+            "Would write 6 bytes into mocha-red.txt",
+        ]
+    );
+
+    let output = render_with(
+        &[
+            "-f",
+            "espresso",
+            "--palette",
+            "tests/fixtures/palette/palette.yaml",
+            "--color-overrides",
+            r#"{"espresso": {"red": "f00"}}"#,
+        ],
+        "{{ flavor.name }} {{ base.hex }} {{ red.hex }} {{ flavors | length }}",
+    );
+    assert_eq!(output, "Espresso 000000 ff0000 2");
+
+    render_err(
+        &[
+            "-f",
+            "latte",
+            "--palette",
+            "tests/fixtures/palette/palette.yaml",
+        ],
+        "{{ red.hex }}",
+    );
+
+    // plain hex overrides must name a color in the flavors they target
+    let args = |overrides| {
+        [
+            "--palette",
+            "tests/fixtures/palette/palette.yaml",
+            "--color-overrides",
+            overrides,
+        ]
+    };
+    let output = render_with(
+        &args(r#"{"espresso": {"crema": "123456"}}"#),
+        "{{ flavors.espresso.colors.crema.hex }}",
+    );
+    assert_eq!(output, "123456");
+    for (overrides, message) in [
+        (
+            r#"{"mocha": {"crema": "123456"}}"#,
+            "Unknown color `crema` in `mocha` color overrides",
+        ),
+        (
+            r#"{"all": {"crema": "123456"}}"#,
+            "Unknown color `crema` in `all` color overrides",
+        ),
+    ] {
+        let stderr = render_err(&args(overrides), "{{ flavors | length }}");
+        assert!(stderr.contains(message), "{stderr}");
+    }
+}
+
+#[test]
+fn test_derived_flavors() {
+    let output = run(&["--dry-run", "tests/fixtures/derive/derive.j2"]);
+    assert_eq!(
+        sorted_lines(&output),
+        [
+            "Would write 31 bytes into latte.txt",
+            "Would write 31 bytes into mocha.txt",
+            "Would write 33 bytes into frappe.txt",
+            "Would write 35 bytes into macchiato.txt",
+            "Would write 36 bytes into mocha-oled.txt",
+        ]
+    );
+
+    // --flavor limits the matrix to the derived flavor
+    let output = run(&[
+        "--dry-run",
+        "tests/fixtures/derive/derive.j2",
+        "-f",
+        "mocha-oled",
+    ]);
+    assert_eq!(output, "Would write 36 bytes into mocha-oled.txt\n");
+
+    // the rendered file has the overridden base and mocha's other colors
     let mut cmd = Command::cargo_bin("whiskers2").expect("binary exists");
     let assert = cmd
-        .args(["--dry-run", "tests/fixtures/multifile.j2"])
+        .current_dir("tests/fixtures/derive")
+        .args(["derive.j2", "-f", "mocha-oled", "--check"])
         .assert();
     assert.success();
+
+    let template = r#"---
+whiskers:
+  derive:
+    mocha-oled:
+      from: mocha
+      colors:
+        base: "000000"
+        glow: {hex: "ff00ff", accent: true}
+---
+{{ flavor.name }} {{ flavor.dark }} {{ base.hex }} {{ text.hex }} {{ glow.hex }} {{ flavors | length }}"#;
+    assert_eq!(
+        render("mocha-oled", template),
+        "mocha-oled true 000000 cdd6f4 ff00ff 5"
+    );
+
+    // overrides of the parent flavor carry over to the derived one, and
+    // overrides of the derived flavor apply on top of its own colors
+    let template = r#"---
+whiskers:
+  version: 2.0.0
+  capitalize_hex: true
+  derive:
+    mocha-oled:
+      from: mocha
+      colors:
+        base: "000000"
+  color_overrides:
+    mocha:
+      text: "111111"
+      base: "222222"
+    mocha-oled:
+      glow: {hex: "ff00ff"}
+---
+{{ base.hex }} {{ text.hex }} {{ red.hex }} {{ glow.hex }} {{ flavors.mocha.colors.glow is defined }}"#;
+    let output = render_with(
+        &[
+            "-f",
+            "mocha-oled",
+            "--color-overrides",
+            r#"{"mocha": {"red": "333333"}, "mocha-oled": {"base": "444444"}}"#,
+        ],
+        template,
+    );
+    assert_eq!(output, "444444 111111 333333 FF00FF false");
+    assert_eq!(
+        render("mocha-oled", template),
+        "000000 111111 F38BA8 FF00FF false"
+    );
+
+    let template = r"---
+whiskers:
+  derive:
+    mocha-oled:
+      from: espresso
+---
+{{ flavors | length }}";
+    let stderr = render_err(&[], template);
+    assert!(stderr.contains("unknown flavor `espresso`"), "{stderr}");
+}
+
+#[test]
+fn test_frontmatter_color_overrides() {
+    let template = r#"---
+whiskers:
+  color_overrides:
+    all:
+      surface0: "111111"
+      glow: {hex: "ff00ff", accent: true}
+    mocha:
+      surface0: "222222"
+      red: "333333"
+---
+{{ surface0.hex }} {{ red.hex }} {{ glow.hex }}"#;
+
+    assert_eq!(render("mocha", template), "222222 333333 ff00ff");
+    assert_eq!(render("latte", template), "111111 d20f39 ff00ff");
+
+    // command-line overrides take precedence, even "all" over a flavor
+    let output = render_with(
+        &[
+            "-f",
+            "mocha",
+            "--color-overrides",
+            r#"{"all": {"surface0": "444444"}, "mocha": {"glow": "555555"}}"#,
+        ],
+        template,
+    );
+    assert_eq!(output, "444444 333333 555555");
+
+    let template = r#"---
+whiskers:
+  color_overrides:
+    mocha:
+      not_a_color: "000000"
+---
+{{ base.hex }}"#;
+    let stderr = render_err(&["-f", "mocha"], template);
+    assert!(
+        stderr.contains("Frontmatter color overrides are invalid"),
+        "{stderr}"
+    );
 }